@@ -10,13 +10,43 @@ mod util;
 
 use util::*;
 
+fn usage() -> ! {
+    println!("program <day> <part> [--param key=value]...");
+    println!("program snafu <encode|decode> [value]...");
+    std::process::exit(1);
+}
+
+// Converts the given values, or every line of stdin if there are none
+fn snafu(args:&[String]) {
+    let conversion = Conversion::parse(&args[0]).unwrap_or_else(|| usage());
+
+    let mut input = String::new();
+    let values = if args.len() > 1 {
+        args[1..].iter().map(|arg| arg.as_str()).collect::<Vec<_>>()
+    } else {
+        std::io::stdin().read_to_string(&mut input).expect("Failed to fetch input...");
+        input.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).collect()
+    };
+
+    let (table, failed) = conversion_table(conversion, &values);
+    println!("{}", table);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
 fn main() {
 
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 3 {
-        println!("program <day> <part>");
-        std::process::exit(1);
+    if args.len() < 3 {
+        usage();
+    }
+
+    if args[1] == "snafu" {
+        snafu(&args[2..]);
+        return;
     }
 
     // Read arguments
@@ -27,12 +57,38 @@ fn main() {
         _ => panic!("illegal part arguments!")
     };
 
+    let mut assignments = vec![];
+    let mut rest = args[3..].iter();
+    while let Some(arg) = rest.next() {
+        match (arg.as_str(), rest.next()) {
+            ("--param", Some(assignment)) => assignments.push(assignment.as_str()),
+            _ => usage(),
+        }
+    }
+
+    let declared = get_params(day, part);
+    let params = match Params::parse(&declared, &assignments) {
+        Ok(params) => params,
+        Err(error) => {
+            println!("{}", error);
+            println!("parameters for day {}:", day);
+            for param in declared.iter() {
+                println!("  {}={}  {}", param.name, param.default, param.description);
+            }
+            std::process::exit(1);
+        }
+    };
+
     // Read input
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).expect("Failed to fetch input...");
 
-    let result = get_solution(day, part, input);
-
-    println!("{}",result);
+    match get_solution(day, part, input, &params) {
+        Ok(result) => println!("{}",result),
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+    }
 
 }
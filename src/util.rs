@@ -23,6 +23,13 @@ mod day_22;
 mod day_23;
 mod day_24;
 mod day_25;
+mod numeral;
+
+pub use self::day_25::{conversion_table, Conversion};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 #[derive(PartialEq,Copy, Clone)]
 pub enum Part {
@@ -30,41 +37,235 @@ pub enum Part {
     Part2,
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub enum Value {
+    Int(i64),
+    // Comma separated on the command line
+    Ints(Vec<i64>),
+    Text(String),
+    // One of the declared choices
+    Choice(String),
+}
+
+// A named puzzle parameter declared by a solver, with its default value
+pub struct Param {
+    pub name:&'static str,
+    pub description:&'static str,
+    pub default:Value,
+    pub choices:&'static [&'static str],
+    // Allowed integer values
+    pub range:RangeInclusive<i64>,
+}
+
+pub struct Params {
+    values:HashMap<&'static str, Value>,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Ints(values) => write!(f, "{}", values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",")),
+            Value::Text(value) => write!(f, "{}", value),
+            Value::Choice(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Param {
+    pub fn int(name:&'static str, description:&'static str, default:i64) -> Param {
+        Self::ranged(name, description, default, i64::MIN..=i64::MAX)
+    }
+
+    pub fn ranged(name:&'static str, description:&'static str, default:i64, range:RangeInclusive<i64>) -> Param {
+        Param{name, description, default:Value::Int(default), choices:&[], range}
+    }
+
+    pub fn ints(name:&'static str, description:&'static str, default:&[i64]) -> Param {
+        Param{name, description, default:Value::Ints(default.to_vec()), choices:&[], range:i64::MIN..=i64::MAX}
+    }
+
+    pub fn text(name:&'static str, description:&'static str, default:&str) -> Param {
+        Param{name, description, default:Value::Text(default.to_string()), choices:&[], range:i64::MIN..=i64::MAX}
+    }
+
+    // The first choice is the default
+    pub fn choice(name:&'static str, description:&'static str, choices:&'static [&'static str]) -> Param {
+        Param{name, description, default:Value::Choice(choices[0].to_string()), choices, range:i64::MIN..=i64::MAX}
+    }
+}
+
+impl Params {
+    // Declared defaults, overridden by "key=value" assignments
+    pub fn parse(declared:&[Param], assignments:&[&str]) -> Result<Params, String> {
+        let mut values = declared.iter()
+            .map(|param| (param.name, param.default.clone()))
+            .collect::<HashMap<_,_>>();
+
+        for assignment in assignments {
+            let (key, value) = assignment.split_once('=')
+                .ok_or(format!("expected key=value, got '{}'", assignment))?;
 
+            let param = declared.iter().find(|param| param.name == key)
+                .ok_or(format!("unknown parameter '{}'", key))?;
 
-pub fn get_solution(day:u8, part:Part, input:String) -> String {
+            let value = match param.default {
+                Value::Int(_) => match value.parse::<i64>() {
+                    Ok(number) if param.range.contains(&number) => Value::Int(number),
+                    Ok(_) if *param.range.end() == i64::MAX =>
+                        return Err(format!("parameter '{}' must be at least {}, got '{}'", key, param.range.start(), value)),
+                    Ok(_) => return Err(format!("parameter '{}' must be between {} and {}, got '{}'", key, param.range.start(), param.range.end(), value)),
+                    Err(_) => return Err(format!("parameter '{}' expects an integer, got '{}'", key, value)),
+                },
+                Value::Ints(_) => Value::Ints(value.split(',').map(|item| item.trim().parse::<i64>()).collect::<Result<_,_>>()
+                    .map_err(|_| format!("parameter '{}' expects comma separated integers, got '{}'", key, value))?),
+                Value::Text(_) => Value::Text(value.to_string()),
+                Value::Choice(_) if param.choices.contains(&value) => Value::Choice(value.to_string()),
+                Value::Choice(_) => return Err(format!("parameter '{}' expects one of {}, got '{}'", key, param.choices.join("|"), value)),
+            };
+
+            values.insert(param.name, value);
+        }
+
+        Ok(Params{values})
+    }
+
+    pub fn defaults(declared:&[Param]) -> Params {
+        Self::parse(declared, &[]).unwrap()
+    }
+
+    pub fn int(&self, name:&str) -> i64 {
+        match self.values.get(name) {
+            Some(Value::Int(value)) => *value,
+            _ => panic!("undeclared integer parameter:{}", name),
+        }
+    }
+
+    pub fn ints(&self, name:&str) -> &[i64] {
+        match self.values.get(name) {
+            Some(Value::Ints(values)) => values,
+            _ => panic!("undeclared integer list parameter:{}", name),
+        }
+    }
+
+    pub fn text(&self, name:&str) -> &str {
+        match self.values.get(name) {
+            Some(Value::Text(value)) => value,
+            _ => panic!("undeclared text parameter:{}", name),
+        }
+    }
+
+    pub fn choice(&self, name:&str) -> &str {
+        match self.values.get(name) {
+            Some(Value::Choice(value)) => value,
+            _ => panic!("undeclared choice parameter:{}", name),
+        }
+    }
+}
+
+pub fn get_params(day:u8, part:Part) -> Vec<Param> {
     match day {
-        1 => day_01::solve(input, part),
-        2 => day_02::solve(input, part),
-        3 => day_03::solve(input, part),
-        4 => day_04::solve(input, part),
-        5 => day_05::solve(input, part),
-        6 => day_06::solve(input, part),
-        7 => day_07::solve(input, part),
-        8 => day_08::solve(input, part),
-        9 => day_09::solve(input, part),
-        10 => day_10::solve(input, part),
-        11 => day_11::solve(input, part),
-        12 => day_12::solve(input, part),
-        13 => day_13::solve(input, part),
-        14 => day_14::solve(input, part),
-        15 => day_15::solve(input, part),
-        16 => day_16::solve(input, part),
-        17 => day_17::solve(input, part),
-        18 => day_18::solve(input, part),
-        19 => day_19::solve(input, part),
-        20 => day_20::solve(input, part),
-        21 => day_21::solve(input, part),
-        22 => day_22::solve(input, part),
-        23 => day_23::solve(input, part),
-        24 => day_24::solve(input, part),
-        25 => day_25::solve(input, part),
+        7 => day_07::params(part),
+        11 => day_11::params(part),
+        15 => day_15::params(part),
+        17 => day_17::params(part),
+        19 => day_19::params(part),
+        20 => day_20::params(part),
+        21 => day_21::params(part),
+        23 => day_23::params(part),
+        _ => vec![],
+    }
+}
+
+
+
+pub fn get_solution(day:u8, part:Part, input:String, params:&Params) -> Result<String, String> {
+    match day {
+        1 => Ok(day_01::solve(input, part)),
+        2 => Ok(day_02::solve(input, part)),
+        3 => Ok(day_03::solve(input, part)),
+        4 => Ok(day_04::solve(input, part)),
+        5 => Ok(day_05::solve(input, part)),
+        6 => Ok(day_06::solve(input, part)),
+        7 => day_07::solve_with_params(input, part, params),
+        8 => Ok(day_08::solve(input, part)),
+        9 => Ok(day_09::solve(input, part)),
+        10 => Ok(day_10::solve(input, part)),
+        11 => day_11::solve_with_params(input, part, params),
+        12 => Ok(day_12::solve(input, part)),
+        13 => Ok(day_13::solve(input, part)),
+        14 => Ok(day_14::solve(input, part)),
+        15 => day_15::solve_with_params(input, part, params),
+        16 => Ok(day_16::solve(input, part)),
+        17 => day_17::solve_with_params(input, part, params),
+        18 => Ok(day_18::solve(input, part)),
+        19 => day_19::solve_with_params(input, part, params),
+        20 => day_20::solve_with_params(input, part, params),
+        21 => day_21::solve_with_params(input, part, params),
+        22 => Ok(day_22::solve(input, part)),
+        23 => day_23::solve_with_params(input, part, params),
+        24 => Ok(day_24::solve(input, part)),
+        25 => Ok(day_25::solve(input, part)),
 
         _ => panic!("..."),
     }
 
 }
 
+// Plain (ASCII) PGM image, values above max are drawn as max
+pub fn to_pgm(rows:&[Vec<usize>], max:usize) -> String {
+    let width = rows.first().map_or(0, |row| row.len());
+    let mut pgm = format!("P2\n{} {}\n{}\n", width, rows.len(), max);
 
+    for row in rows.iter() {
+        let line = row.iter()
+            .map(|value| value.min(&max).to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        pgm.push_str(&line);
+        pgm.push('\n');
+    }
 
+    pgm
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let declared = vec![Param::int("rounds", "Number of rounds", 10)];
+
+        assert_eq!(10, Params::defaults(&declared).int("rounds"));
+        assert_eq!(25, Params::parse(&declared, &["rounds=25"]).unwrap().int("rounds"));
+        assert_eq!(Err("unknown parameter 'minutes'".to_string()), Params::parse(&declared, &["minutes=3"]).map(|_| ()));
+        assert_eq!(Err("parameter 'rounds' expects an integer, got 'ten'".to_string()), Params::parse(&declared, &["rounds=ten"]).map(|_| ()));
+        assert_eq!(Err("expected key=value, got 'rounds'".to_string()), Params::parse(&declared, &["rounds"]).map(|_| ()));
+
+        let declared = vec![Param::ranged("rounds", "Number of rounds", 10, 0..=i64::MAX), Param::ranged("row", "Row", 0, -5..=5)];
+
+        assert_eq!(0, Params::parse(&declared, &["rounds=0"]).unwrap().int("rounds"));
+        assert_eq!(-5, Params::parse(&declared, &["row=-5"]).unwrap().int("row"));
+        assert_eq!(Err("parameter 'rounds' must be at least 0, got '-1'".to_string()), Params::parse(&declared, &["rounds=-1"]).map(|_| ()));
+        assert_eq!(Err("parameter 'row' must be between -5 and 5, got '6'".to_string()), Params::parse(&declared, &["row=6"]).map(|_| ()));
 
+        let declared = vec![Param::ints("offsets", "Offsets", &[1, 2])];
+
+        assert_eq!(vec![1, 2], Params::defaults(&declared).ints("offsets"));
+        assert_eq!(vec![5, -3, 7], Params::parse(&declared, &["offsets=5,-3,7"]).unwrap().ints("offsets"));
+        assert_eq!(Err("parameter 'offsets' expects comma separated integers, got '5,x'".to_string()), Params::parse(&declared, &["offsets=5,x"]).map(|_| ()));
+
+        let declared = vec![Param::text("relief", "Relief", "old / 3")];
+
+        assert_eq!("old / 3", Params::defaults(&declared).text("relief"));
+        assert_eq!("old - 1 = 2", Params::parse(&declared, &["relief=old - 1 = 2"]).unwrap().text("relief"));
+
+        let declared = vec![Param::choice("search", "Search algorithm", &["fast", "slow"])];
+
+        assert_eq!("fast", Params::defaults(&declared).choice("search"));
+        assert_eq!("slow", Params::parse(&declared, &["search=slow"]).unwrap().choice("search"));
+        assert_eq!(Err("parameter 'search' expects one of fast|slow, got 'other'".to_string()), Params::parse(&declared, &["search=other"]).map(|_| ()));
+    }
+}
@@ -9,30 +9,124 @@ pub fn solve(input : String, part: Part) -> String {
     }
 }
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Move {
+    count:usize,
+    from:usize,
+    to:usize,
+}
+
+trait CraneModel {
+    // Number of crates lifted at once when `remaining` crates are left to move
+    fn lift_size(&self, remaining:usize) -> usize;
+}
+
+struct OneAtATime {}
+
+struct MultipleAtOnce {}
+
+#[allow(dead_code)]
+struct CapacityLimited {
+    capacity:usize,
+}
+
+impl CraneModel for OneAtATime {
+    fn lift_size(&self, _remaining:usize) -> usize {
+        1
+    }
+}
+
+impl CraneModel for MultipleAtOnce {
+    fn lift_size(&self, remaining:usize) -> usize {
+        remaining
+    }
+}
+
+impl CraneModel for CapacityLimited {
+    fn lift_size(&self, remaining:usize) -> usize {
+        remaining.min(self.capacity)
+    }
+}
+
+struct Crane {
+    stacks:Vec<VecDeque<char>>,
+    model:Box<dyn CraneModel>,
+    history:Vec<Vec<VecDeque<char>>>,
+}
+
+impl Crane {
+    fn new(stacks:Vec<VecDeque<char>>, model:Box<dyn CraneModel>) -> Crane {
+        Crane{history:vec![stacks.clone()], stacks, model}
+    }
+
+    fn step(&mut self, instruction:&Move) -> Result<(), String> {
+        let num_stacks = self.stacks.len();
+        for stack_no in [instruction.from, instruction.to] {
+            if stack_no == 0 || stack_no > num_stacks {
+                return Err(format!("stack {} does not exist, there are {} stacks", stack_no, num_stacks));
+            }
+        }
+
+        let available = self.stacks[instruction.from - 1].len();
+        if available < instruction.count {
+            return Err(format!("cannot move {} crates from stack {} holding {}", instruction.count, instruction.from, available));
+        }
+
+        let mut remaining = instruction.count;
+        while remaining > 0 {
+            let lift_size = self.model.lift_size(remaining).clamp(1, remaining);
+            let from = &mut self.stacks[instruction.from - 1];
+            let items = from.split_off(from.len() - lift_size);
+            self.stacks[instruction.to - 1].extend(items);
+            remaining -= lift_size;
+        }
+
+        self.history.push(self.stacks.clone());
+        Ok(())
+    }
+
+    fn run(&mut self, instructions:&[Move]) -> Result<(), String> {
+        instructions.iter().try_for_each(|instruction| self.step(instruction))
+    }
+
+    fn top_crates(&self) -> String {
+        self.stacks.iter().filter(|stack| !stack.is_empty())
+            .map(|stack| stack.back().unwrap())
+            .collect::<String>()
+    }
+
+    #[allow(dead_code)]
+    fn render(&self) -> String {
+        render_map(&self.stacks)
+    }
+}
 
-fn parse_moves(lines:&Vec<&str>) -> Vec<(usize,usize,usize)> {
+fn parse_moves(lines:&[&str]) -> Vec<Move> {
     lines.iter()
         .filter( |line| line.contains("move"))
         .map(|line| {
             let v:Vec<&str> = line.split(' ').collect();
-            let n = v[1].parse::<usize>().unwrap();
+            let count = v[1].parse::<usize>().unwrap();
             let from = v[3].parse::<usize>().unwrap();
             let to = v[5].parse::<usize>().unwrap();
-            (n, from-1, to-1)
+            Move{count, from, to}
         })
         .collect::<Vec<_>>()
 }
 
-fn parse_map(lines:&Vec<&str>) -> Vec<VecDeque<char>> {
+fn parse_map(lines:&[&str]) -> Vec<VecDeque<char>> {
     let stack_lines = lines.iter()
         .filter( |line| line.contains("["))
         .map(|line| line.as_bytes())
         .collect::<Vec<_>>();
 
-    let num_cols = stack_lines.iter().map(|line| (line.len()-1)/4).max().unwrap();
+    // Prefer the numbered footer, as it also counts stacks that are empty
+    let num_stacks = lines.iter()
+        .find(|line| line.trim_start().starts_with('1'))
+        .map(|line| line.split_whitespace().count())
+        .unwrap_or_else(|| stack_lines.iter().map(|line| (line.len()+1)/4).max().unwrap_or(0));
 
-    let mut stacks = vec![];
-    (0..=num_cols).into_iter().for_each(|_| stacks.push(VecDeque::new()));
+    let mut stacks = vec![VecDeque::new(); num_stacks];
 
     stack_lines.iter()
         .for_each(|stack_line| {
@@ -48,32 +142,43 @@ fn parse_map(lines:&Vec<&str>) -> Vec<VecDeque<char>> {
     stacks
 }
 
-fn run_instructions(mut stacks:Vec<VecDeque<char>>, instructions:Vec<(usize,usize,usize)>, preserve_order:bool) -> String {
-    for &(num, from, to) in instructions.iter() {
-        let mut items = (0..num).into_iter()
-            .map(|_| stacks.get_mut(from).unwrap().pop_back().unwrap())
-            .collect::<Vec<_>>();
+fn render_map(stacks:&[VecDeque<char>]) -> String {
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let mut lines = vec![];
 
-        if preserve_order {
-            items.reverse();
-        }
-
-        items.into_iter()
-            .for_each(|item| stacks.get_mut(to).unwrap().push_back(item));
+    for level in (0..height).rev() {
+        let line = stacks.iter()
+            .map(|stack| match stack.get(level) {
+                Some(ch) => format!("[{}]", ch),
+                None => "   ".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        lines.push(line.trim_end().to_string());
     }
 
-    stacks.iter().filter(|stack| !stack.is_empty())
-        .map(|stack| stack.back().unwrap())
-        .collect::<String>()
+    let footer = (1..=stacks.len())
+        .map(|stack_no| format!(" {} ", stack_no))
+        .collect::<Vec<_>>()
+        .join(" ");
+    lines.push(footer.trim_end().to_string());
+
+    lines.join("\n")
+}
+
+fn run_instructions(stacks:Vec<VecDeque<char>>, instructions:Vec<Move>, model:Box<dyn CraneModel>) -> String {
+    let mut crane = Crane::new(stacks, model);
+    crane.run(&instructions).unwrap();
+    crane.top_crates()
 }
 
 fn part1(lines:Vec<&str>) -> String {
-    run_instructions(parse_map(&lines), parse_moves(&lines), false)
+    run_instructions(parse_map(&lines), parse_moves(&lines), Box::new(OneAtATime{}))
 }
 
 
 fn part2(lines:Vec<&str>) -> String {
-    run_instructions(parse_map(&lines), parse_moves(&lines), true)
+    run_instructions(parse_map(&lines), parse_moves(&lines), Box::new(MultipleAtOnce{}))
 }
 
 
@@ -130,4 +235,72 @@ move 1 from 1 to 2";
 
         assert_eq!("CJVLJQPHS", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_crane_history() {
+        let lines = "    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2".lines().collect::<Vec<_>>();
+
+        let mut crane = Crane::new(parse_map(&lines), Box::new(CapacityLimited{capacity:2}));
+        crane.run(&parse_moves(&lines)).unwrap();
+
+        assert_eq!(5, crane.history.len());
+        assert_eq!(crane.history[0][0], vec!['Z', 'N']);
+        assert_eq!(crane.history[2][2], vec!['P', 'N', 'D', 'Z']);
+        assert_eq!("MCZ", crane.top_crates());
+        assert_eq!("        [Z]
+        [D]
+        [N]
+[M] [C] [P]
+ 1   2   3", crane.render());
+    }
+
+    #[test]
+    fn test_crane_errors() {
+        let lines = "[A]
+ 1   2".lines().collect::<Vec<_>>();
+
+        let mut crane = Crane::new(parse_map(&lines), Box::new(OneAtATime{}));
+
+        assert_eq!(Err("stack 4 does not exist, there are 2 stacks".to_string()), crane.step(&Move{count:1, from:1, to:4}));
+        assert_eq!(Err("cannot move 2 crates from stack 1 holding 1".to_string()), crane.step(&Move{count:2, from:1, to:1}));
+        assert_eq!(1, crane.history.len());
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let input = include_str!("../../input/input_05.txt");
+        let drawing = input.split("\n\n").next().unwrap();
+        let lines = input.lines().collect::<Vec<_>>();
+
+        assert_eq!(drawing, render_map(&parse_map(&lines)));
+    }
+
+    #[test]
+    fn test_render_parse_property() {
+        // Simple LCG to generate deterministic pseudo random stacks
+        let mut seed = 12345_u64;
+        let mut next = |limit:u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % limit
+        };
+
+        for _ in 0..500 {
+            let stacks = (0..=next(9))
+                .map(|_| (0..next(12)).map(|_| (b'A' + next(26) as u8) as char).collect::<VecDeque<_>>())
+                .collect::<Vec<_>>();
+
+            let rendering = render_map(&stacks);
+            let lines = rendering.lines().collect::<Vec<_>>();
+
+            assert_eq!(stacks, parse_map(&lines), "{}", rendering);
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+use std::io::{BufReader, Bytes, Read};
 use super::Part;
 
 pub fn solve(input : String, part: Part) -> String {
@@ -8,20 +10,72 @@ pub fn solve(input : String, part: Part) -> String {
     }
 }
 
-fn find_marker(input : String, market_size:usize) -> String {
-    let buffer = input.as_bytes();
+struct MarkerDetector {
+    window_size:usize,
+    window:VecDeque<u8>,
+    counts:[u32;256],
+    distinct:usize,
+    position:usize,
+}
+
+impl MarkerDetector {
+    fn new(window_size:usize) -> MarkerDetector {
+        MarkerDetector{window_size, window:VecDeque::with_capacity(window_size + 1), counts:[0;256], distinct:0, position:0}
+    }
+
+    // Consumes one byte, returns true if the last window_size bytes are all different
+    fn push(&mut self, byte:u8) -> bool {
+        self.position += 1;
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.distinct += 1;
+        }
+
+        if self.window.len() > self.window_size {
+            let oldest = self.window.pop_front().unwrap() as usize;
+            self.counts[oldest] -= 1;
+            if self.counts[oldest] == 0 {
+                self.distinct -= 1;
+            }
+        }
+
+        self.window.len() == self.window_size && self.distinct == self.window_size
+    }
+}
+
+struct Markers<R:Read> {
+    bytes:Bytes<BufReader<R>>,
+    detector:MarkerDetector,
+}
 
-    for i in 0..buffer.len() - market_size {
-        let unique_symbols = buffer[i..(i+ market_size)].iter()
-            .fold(0 as u32, |acc,next| acc | (1 << (*next - 'a' as u8)))
-            .count_ones();
+impl <R:Read> Iterator for Markers<R> {
+    type Item = std::io::Result<usize>;
 
-        if unique_symbols == market_size as u32 {
-            return (i+ market_size).to_string()
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            match byte {
+                Ok(byte) if self.detector.push(byte) => return Some(Ok(self.detector.position)),
+                Ok(_) => continue,
+                Err(err) => return Some(Err(err)),
+            }
         }
+
+        None
     }
+}
+
+// Positions (number of bytes consumed) after each window of unique bytes
+fn find_markers<R:Read>(reader:R, window_size:usize) -> Markers<R> {
+    Markers{bytes:BufReader::new(reader).bytes(), detector:MarkerDetector::new(window_size)}
+}
 
-    panic!("No solution");
+fn find_marker(input : String, marker_size:usize) -> String {
+    find_markers(input.trim().as_bytes(), marker_size)
+        .next()
+        .expect("No solution")
+        .unwrap()
+        .to_string()
 }
 
 fn part1(input : String) -> String {
@@ -83,4 +137,53 @@ mod tests {
 
         assert_eq!("3256", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_all_markers() {
+        let markers = find_markers("abcabcdabcdeab".as_bytes(), 4)
+            .map(|marker| marker.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![7, 8, 9, 10, 11, 12, 13, 14], markers);
+        assert_eq!(0, find_markers("aaaa".as_bytes(), 2).count());
+    }
+
+    #[test]
+    fn test_large_window() {
+        let input = (0..=255_u8).chain(0..=255_u8).collect::<Vec<_>>();
+
+        assert_eq!(257, find_markers(input.as_slice(), 256).count());
+        assert_eq!(Some(100), find_markers(input.as_slice(), 100).next().map(|marker| marker.unwrap()));
+    }
+
+    #[test]
+    fn test_stream() {
+        // Synthetic stream repeating "a" + 16 distinct letters, generated on the fly
+        struct Synthetic {
+            remaining:usize,
+            offset:usize,
+        }
+
+        impl Read for Synthetic {
+            fn read(&mut self, buf:&mut [u8]) -> std::io::Result<usize> {
+                let pattern = b"aabcdefghijklmnop";
+                let len = buf.len().min(self.remaining);
+                for byte in buf[..len].iter_mut() {
+                    *byte = pattern[self.offset % pattern.len()];
+                    self.offset += 1;
+                }
+                self.remaining -= len;
+                Ok(len)
+            }
+        }
+
+        let markers = find_markers(Synthetic{remaining:17 * 1_000_000, offset:0}, 16)
+            .map(|marker| marker.unwrap())
+            .collect::<Vec<_>>();
+
+        // "a...p" and "b...pa" are the unique windows in each repetition
+        assert_eq!(2 * 1_000_000 - 1, markers.len());
+        assert_eq!(17, markers[0]);
+        assert_eq!(17 * 1_000_000, *markers.last().unwrap());
+    }
 }
@@ -1,75 +1,331 @@
-use std::collections::VecDeque;
-use super::Part;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
+
+pub fn params(part:Part) -> Vec<Param> {
+    match part {
+        Part::Part1 => vec![],
+        Part::Part2 => vec![
+            Param::ranged("disk", "Total disk space", 70_000_000, 0..=i64::MAX),
+            Param::ranged("update", "Unused space the update needs", 30_000_000, 0..=i64::MAX),
+            Param::choice("strategy", "Which dirs to delete", &["smallest-dir", "minimal-set"]),
+        ],
+    }
+}
+
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
     match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
+        Part::Part1 => Ok(part1(input)),
+        Part::Part2 => part2(input, params.int("disk") as u64, params.int("update") as u64, Strategy::parse(params.choice("strategy")))
     }
 }
 
-fn parse_file_tree(input:String) -> Vec<u32> {
-    let mut lines:VecDeque<&str> = input.lines().collect();
-    let mut stack:VecDeque<u32> = VecDeque::new();
-    let mut dir_sizes = vec![];
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Strategy {
+    // The smallest single dir that frees enough
+    SmallestDir,
+    // Any dirs together, freeing as little as possible
+    MinimalSet,
+}
 
-    while !stack.is_empty() || !lines.is_empty() {
+#[derive(Debug, PartialEq)]
+struct DeletionPlan {
+    paths:Vec<String>,
+    freed:u64,
+}
 
-        // Parse commands
-        while !lines.is_empty() {
-            let cmd = lines.pop_front().unwrap().split(' ').collect::<Vec<_>>();
+#[derive(Debug)]
+struct Dir {
+    path:String,
+    parent:Option<usize>,
+    dirs:BTreeMap<String,usize>,
+    files:BTreeMap<String,u64>,
+    listed:bool,
+}
 
-            if cmd[0].eq("$") && cmd[1].eq("cd") {
-                // Change of dir
-                if cmd[2].eq("..") {
-                    // Break loop and pop stack
-                    break;
-                } else {
-                    // Add new sub dir
-                    stack.push_front(0_u32);
+#[derive(Debug)]
+struct FileSystem {
+    dirs:Vec<Dir>,
+    inconsistencies:Vec<String>,
+}
+
+impl Dir {
+    fn new(path:String, parent:Option<usize>) -> Dir {
+        Dir{path, parent, dirs:BTreeMap::new(), files:BTreeMap::new(), listed:false}
+    }
+}
+
+impl Strategy {
+    fn parse(name:&str) -> Strategy {
+        match name {
+            "smallest-dir" => Strategy::SmallestDir,
+            "minimal-set" => Strategy::MinimalSet,
+            _ => panic!("unknown strategy:{}", name),
+        }
+    }
+}
+
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem{dirs:vec![Dir::new("/".to_string(), None)], inconsistencies:vec![]}
+    }
+
+    fn parse(input:&str) -> FileSystem {
+        let mut fs = FileSystem::new();
+        let mut cwd = 0;
+        let mut listing:Option<(BTreeSet<String>, BTreeMap<String,u64>)> = None;
+
+        for line in input.lines().filter(|line| !line.is_empty()) {
+            let tokens = line.split(' ').collect::<Vec<_>>();
+
+            if tokens[0].eq("$") {
+                // A new command ends any ongoing listing
+                if let Some((dirs, files)) = listing.take() {
+                    fs.apply_listing(cwd, dirs, files);
+                }
+
+                match tokens[1] {
+                    "cd" => cwd = match tokens[2] {
+                        "/" => 0,
+                        ".." => fs.dirs[cwd].parent.unwrap_or(0),
+                        name => fs.get_or_create_dir(cwd, name),
+                    },
+                    "ls" => listing = Some((BTreeSet::new(), BTreeMap::new())),
+                    cmd => panic!("unexpected command:{}", cmd),
                 }
-            } else if cmd[0].eq("$") && cmd[1].eq("ls") {
-                // List nodes in dir
-                while !lines.is_empty() && !lines.front().unwrap().starts_with("$") {
-                    let node = lines.pop_front().unwrap().split(' ').collect::<Vec<_>>();
-                    if node[0].ne("dir") {
-                        // File -> add size
-                        *stack.front_mut().unwrap() += node[0].parse::<u32>().unwrap();
-                    }
+            } else if let Some((dirs, files)) = listing.as_mut() {
+                if tokens[0].eq("dir") {
+                    dirs.insert(tokens[1].to_string());
+                } else {
+                    files.insert(tokens[1].to_string(), tokens[0].parse::<u64>().unwrap());
                 }
+            } else {
+                panic!("output without command:{}", line);
             }
         }
 
-        // Pop current dir
-        let size = stack.pop_front().unwrap();
+        if let Some((dirs, files)) = listing.take() {
+            fs.apply_listing(cwd, dirs, files);
+        }
+
+        fs
+    }
 
-        if !stack.is_empty() {
-            // And add its' size to parent
-            *stack.front_mut().unwrap() += size;
+    fn get_or_create_dir(&mut self, parent:usize, name:&str) -> usize {
+        if let Some(&index) = self.dirs[parent].dirs.get(name) {
+            return index;
         }
 
-        // Add to result
-        dir_sizes.push(size);
+        let path = if parent == 0 {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", self.dirs[parent].path, name)
+        };
+
+        let index = self.dirs.len();
+        self.dirs.push(Dir::new(path, Some(parent)));
+        self.dirs[parent].dirs.insert(name.to_string(), index);
+        index
+    }
+
+    fn apply_listing(&mut self, cwd:usize, dirs:BTreeSet<String>, files:BTreeMap<String,u64>) {
+        if self.dirs[cwd].listed {
+            // Compare with what was seen the last time this dir was listed
+            let dir = &self.dirs[cwd];
+            let same_dirs = dir.dirs.keys().eq(dirs.iter());
+            if !same_dirs || dir.files != files {
+                self.inconsistencies.push(format!("{}: listing differs from previous listing", dir.path));
+            }
+        } else if let Some(name) = self.dirs[cwd].dirs.keys().find(|name| !dirs.contains(*name)) {
+            // Entered a sub dir before listing its' parent
+            self.inconsistencies.push(format!("{}: visited dir {} missing from listing", self.dirs[cwd].path, name));
+        }
+
+        for name in files.keys().filter(|name| dirs.contains(*name)) {
+            self.inconsistencies.push(format!("{}: {} listed as both file and dir", self.dirs[cwd].path, name));
+        }
+
+        for name in dirs.iter() {
+            self.get_or_create_dir(cwd, name);
+        }
+
+        let dir = &mut self.dirs[cwd];
+        dir.files.extend(files);
+        dir.listed = true;
+    }
+
+    fn find_dir(&self, path:&str) -> Option<usize> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(0, |index, name| self.dirs[index].dirs.get(name).copied())
+    }
+
+    fn dir_sizes(&self) -> Vec<u64> {
+        // Sub dirs are always created after their parent, so a reverse scan sees children first
+        let mut sizes = self.dirs.iter()
+            .map(|dir| dir.files.values().sum::<u64>())
+            .collect::<Vec<_>>();
+
+        for index in (1..self.dirs.len()).rev() {
+            let parent = self.dirs[index].parent.unwrap();
+            sizes[parent] += sizes[index];
+        }
+
+        sizes
+    }
+
+    #[allow(dead_code)]
+    fn du(&self, path:&str) -> Option<u64> {
+        self.find_dir(path).map(|index| self.dir_sizes()[index])
+    }
+
+    fn find(&self, min_size:Option<u64>, max_size:Option<u64>) -> Vec<(&str,u64)> {
+        let mut result = self.dirs.iter()
+            .zip(self.dir_sizes())
+            .filter(|(_, size)| min_size.is_none_or(|min| *size >= min))
+            .filter(|(_, size)| max_size.is_none_or(|max| *size <= max))
+            .map(|(dir, size)| (dir.path.as_str(), size))
+            .collect::<Vec<_>>();
+
+        result.sort();
+        result
+    }
+
+    fn plan_deletion(&self, disk_size:u64, update_size:u64, strategy:Strategy) -> Option<DeletionPlan> {
+        let sizes = self.dir_sizes();
+        let required = (sizes[0] + update_size).saturating_sub(disk_size);
+
+        if required == 0 {
+            return Some(DeletionPlan{paths:vec![], freed:0});
+        }
+
+        let smallest_dir = (0..self.dirs.len())
+            .filter(|&index| sizes[index] >= required)
+            .min_by_key(|&index| sizes[index])?;
+
+        let chosen = match strategy {
+            Strategy::SmallestDir => vec![smallest_dir],
+            Strategy::MinimalSet => self.find_minimal_set(&sizes, required, sizes[smallest_dir]),
+        };
+
+        let mut paths = chosen.iter().map(|&index| self.dirs[index].path.clone()).collect::<Vec<_>>();
+        paths.sort();
+
+        Some(DeletionPlan{paths, freed:chosen.iter().map(|&index| sizes[index]).sum()})
+    }
+
+    // Minimal means the fewest bytes freed, not the fewest dirs deleted
+    fn find_minimal_set(&self, sizes:&[u64], required:u64, limit:u64) -> Vec<usize> {
+        // Dirs in pre-order, with the position following each dir's subtree
+        let mut order = vec![];
+        let mut subtree_end = vec![];
+        self.pre_order(0, &mut order, &mut subtree_end);
+
+        // Most that can be freed by deleting non-overlapping dirs from order[pos..]
+        let mut most = vec![0; order.len() + 1];
+        for pos in (0..order.len()).rev() {
+            most[pos] = most[pos + 1];
+            if sizes[order[pos]] <= limit {
+                most[pos] = most[pos].max(sizes[order[pos]] + most[subtree_end[pos]]);
+            }
+        }
+
+        // Try each sum upwards, failed (pos, sum) states hold for every target so are shared
+        let search = Search{sizes, order:&order, subtree_end:&subtree_end, most:&most};
+        let mut failed = HashSet::new();
+        let mut chosen = vec![];
+
+        (required..=limit).find(|&sum| search.free(0, sum, &mut failed, &mut chosen)).unwrap();
+        chosen
+    }
+
+    fn pre_order(&self, index:usize, order:&mut Vec<usize>, subtree_end:&mut Vec<usize>) {
+        let pos = order.len();
+        order.push(index);
+        subtree_end.push(0);
+
+        for &sub_dir in self.dirs[index].dirs.values() {
+            self.pre_order(sub_dir, order, subtree_end);
+        }
+
+        subtree_end[pos] = order.len();
+    }
+
+    #[allow(dead_code)]
+    fn tree(&self) -> String {
+        let mut output = String::new();
+        self.print_dir(0, "/", 0, &mut output);
+        output
+    }
+
+    fn print_dir(&self, index:usize, name:&str, depth:usize, output:&mut String) {
+        let dir = &self.dirs[index];
+        output.push_str(&format!("{}- {} (dir)\n", "  ".repeat(depth), name));
+
+        // Merge dirs and files, sorted by name
+        let mut entries = dir.dirs.iter().map(|(name, &index)| (name, Some(index), 0))
+            .chain(dir.files.iter().map(|(name, &size)| (name, None, size)))
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for (name, sub_dir, size) in entries {
+            match sub_dir {
+                Some(sub_dir) => self.print_dir(sub_dir, name, depth + 1, output),
+                None => output.push_str(&format!("{}- {} (file, size={})\n", "  ".repeat(depth + 1), name, size)),
+            }
+        }
     }
+}
 
-    dir_sizes
+// Sparse search for sums that can be freed, deleting a dir skips its' subtree
+struct Search<'a> {
+    sizes:&'a [u64],
+    order:&'a [usize],
+    subtree_end:&'a [usize],
+    most:&'a [u64],
+}
+
+impl Search<'_> {
+    // Whether exactly sum can be freed from order[pos..], pushing the deleted dirs
+    fn free(&self, pos:usize, sum:u64, failed:&mut HashSet<(usize,u64)>, chosen:&mut Vec<usize>) -> bool {
+        if sum == 0 {
+            return true;
+        }
+        if sum > self.most[pos] || failed.contains(&(pos, sum)) {
+            return false;
+        }
+
+        let size = self.sizes[self.order[pos]];
+        if size <= sum && self.free(self.subtree_end[pos], sum - size, failed, chosen) {
+            chosen.push(self.order[pos]);
+            return true;
+        }
+        if self.free(pos + 1, sum, failed, chosen) {
+            return true;
+        }
+
+        failed.insert((pos, sum));
+        false
+    }
 }
 
 fn part1(input : String) -> String {
-    parse_file_tree(input).iter()
-        .filter(|&&size| size <= 100000 )
-        .sum::<u32>()
+    FileSystem::parse(&input).find(None, Some(100000)).iter()
+        .map(|(_, size)| size)
+        .sum::<u64>()
         .to_string()
 }
 
 
-fn part2(input : String) -> String {
-    let result = parse_file_tree(input);
-    let used_space = *result.last().unwrap();
-
-    result.iter()
-        .filter(|&&size| (70_000_000 - used_space + size) >= 30_000_000)
-        .min().unwrap().to_string()
+fn part2(input : String, disk_size:u64, update_size:u64, strategy:Strategy) -> Result<String, String> {
+    FileSystem::parse(&input)
+        .plan_deletion(disk_size, update_size, strategy)
+        .map(|plan| plan.freed.to_string())
+        .ok_or(format!("no dir frees enough space for an update of {} on a disk of {}", update_size, disk_size))
 }
 
 
@@ -154,4 +410,124 @@ $ ls
 
         assert_eq!("3579501", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_tree() {
+        let input = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd /
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+        let fs = FileSystem::parse(input);
+
+        assert_eq!("- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+", fs.tree());
+
+        assert_eq!(Some(584), fs.du("/a/e"));
+        assert_eq!(Some(94853), fs.du("/a"));
+        assert_eq!(Some(48381165), fs.du("/"));
+        assert_eq!(None, fs.du("/x"));
+        assert_eq!(vec![("/", 48381165), ("/d", 24933642)], fs.find(Some(1_000_000), None));
+        assert!(fs.inconsistencies.is_empty());
+    }
+
+    #[test]
+    fn test_inconsistencies() {
+        let input = "$ cd /
+$ ls
+dir a
+100 b
+$ cd a
+$ ls
+200 c
+$ cd /
+$ ls
+dir a
+150 b";
+
+        let fs = FileSystem::parse(input);
+
+        assert_eq!(vec!["/: listing differs from previous listing".to_string()], fs.inconsistencies);
+    }
+
+    #[test]
+    fn test_plan_deletion() {
+        let input = include_str!("../../input/input_07.txt");
+        let fs = FileSystem::parse(input);
+
+        let smallest = fs.plan_deletion(70_000_000, 30_000_000, Strategy::SmallestDir).unwrap();
+        let minimal = fs.plan_deletion(70_000_000, 30_000_000, Strategy::MinimalSet).unwrap();
+
+        assert_eq!(3579501, smallest.freed);
+        assert_eq!(1, smallest.paths.len());
+        assert!(minimal.freed <= smallest.freed);
+        assert!(minimal.freed >= fs.du("/").unwrap() - 40_000_000);
+        assert_eq!(minimal.freed, minimal.paths.iter().map(|path| fs.du(path).unwrap()).sum::<u64>());
+    }
+
+    #[test]
+    fn test_plan_deletion_multiple_dirs() {
+        let input = "$ cd /
+$ ls
+dir a
+dir b
+dir c
+$ cd a
+$ ls
+40 x
+$ cd ..
+$ cd b
+$ ls
+35 x
+$ cd ..
+$ cd c
+$ ls
+100 x";
+
+        let fs = FileSystem::parse(input);
+
+        assert_eq!(Some(DeletionPlan{paths:vec!["/c".to_string()], freed:100}),
+                   fs.plan_deletion(200, 70, Strategy::SmallestDir));
+        assert_eq!(Some(DeletionPlan{paths:vec!["/a".to_string(), "/b".to_string()], freed:75}),
+                   fs.plan_deletion(200, 70, Strategy::MinimalSet));
+        assert_eq!(Some(DeletionPlan{paths:vec![], freed:0}),
+                   fs.plan_deletion(500, 45, Strategy::MinimalSet));
+        assert_eq!(None, fs.plan_deletion(100, 200, Strategy::SmallestDir));
+
+        let plan = |assignments:&[&str]| solve_with_params(input.to_string(), Part2, &Params::parse(&params(Part2), assignments).unwrap());
+        assert_eq!(Ok("75".to_string()), plan(&["disk=200", "update=70", "strategy=minimal-set"]));
+        assert_eq!(Ok("100".to_string()), plan(&["disk=200", "update=70"]));
+        assert_eq!(Err("no dir frees enough space for an update of 200 on a disk of 100".to_string()), plan(&["disk=100", "update=200"]));
+    }
 }
@@ -1,5 +1,5 @@
-use std::collections::{HashSet, VecDeque};
-use super::Part;
+use std::collections::HashMap;
+use super::{Part, to_pgm};
 
 pub fn solve(input : String, part: Part) -> String {
 
@@ -15,9 +15,24 @@ struct Pos {
     y:i32,
 }
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Visit {
+    count:usize,
+    first_step:usize,
+}
+
 #[derive(Debug)]
 struct Rope {
-    knots:VecDeque<Pos>,
+    knots:Vec<Pos>,
+    visits:Vec<HashMap<Pos,Visit>>,
+    steps:usize,
+}
+
+#[derive(Debug)]
+struct VisitStats {
+    visits:HashMap<Pos,Visit>,
+    min:Pos,
+    max:Pos,
 }
 
 impl Pos {
@@ -30,109 +45,176 @@ impl Pos {
     }
 
     fn get_dist(&self,other:&Pos) -> u32 {
-        let horizontal_diff = self.x.abs_diff(other.x);
-        let vertical_diff = self.y.abs_diff(other.y);
-
-        return if horizontal_diff == 1 && vertical_diff == 1 {
-            1
-        } else {
-            horizontal_diff + vertical_diff
-        }
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
     }
 }
 
 impl Rope {
 
-    fn new(no_tails:usize) -> Rope {
-        Rope{knots:(0..no_tails).into_iter().map(|_| Pos::initial()).collect()}
+    fn new(no_knots:usize) -> Rope {
+        let start = HashMap::from([(Pos::initial(), Visit{count:1, first_step:0})]);
+        Rope{knots:vec![Pos::initial(); no_knots], visits:vec![start; no_knots], steps:0}
     }
 
-    fn process_moves(&mut self, input:String) -> Vec<Pos> {
-        let mut moves = vec![Pos::initial()];
+    // Returns the position of every knot after each step
+    fn process_moves(&mut self, input:String) -> Vec<Vec<Pos>> {
         input.lines()
-            .for_each(|line| moves.append(&mut self.process_move(line) ));
-        moves
+            .flat_map(|line| self.process_move(line))
+            .collect()
     }
 
-    fn process_move(&mut self, line:&str) -> Vec<Pos> {
-        let mut it = line.split(' ').into_iter();
-        let command = it.next().unwrap().as_bytes()[0] as char;
-        let moves = it.next().unwrap().parse::<u32>().unwrap();
-        let mut tail_moves = vec![];
+    fn process_move(&mut self, line:&str) -> Vec<Vec<Pos>> {
+        let tokens = line.split(' ').collect::<Vec<_>>();
 
-        for _ in 0..moves {
+        if tokens[0].eq("V") {
+            // Arbitrary vector, head moves diagonally until aligned with the target
+            let dx = tokens[1].parse::<i32>().unwrap();
+            let dy = tokens[2].parse::<i32>().unwrap();
+            self.move_by((dx, dy))
+        } else {
+            let delta = Self::get_direction(tokens[0]);
+            let steps = tokens[1].parse::<i32>().unwrap();
+            self.move_by((delta.0 * steps, delta.1 * steps))
+        }
+    }
 
-            // Move tail
-            for tail_index in 0..self.knots.len() {
+    fn get_direction(command:&str) -> (i32,i32) {
+        match command {
+            "R" => (1,0),
+            "L" => (-1,0),
+            "U" => (0,-1),
+            "D" => (0,1),
+            "UR" => (1,-1),
+            "UL" => (-1,-1),
+            "DR" => (1,1),
+            "DL" => (-1,1),
+            _ => panic!("unexpected command:{}", command),
+        }
+    }
 
-                // Pop first knot
-                let mut knot = self.knots.pop_front().unwrap();
+    fn move_by(&mut self, delta:(i32,i32)) -> Vec<Vec<Pos>> {
+        let target = self.knots[0].add_delta(delta);
+        let mut steps = vec![];
 
-                if tail_index == 0 {
-                    // This is the head, move according to command
-                    Self::move_head(&mut knot, command);
-                } else {
-                    // Fetch head
-                    let head = self.knots.back().unwrap();
+        while self.knots[0] != target {
+            let head = self.knots[0];
+            self.step(((target.x - head.x).signum(), (target.y - head.y).signum()));
+            steps.push(self.knots.clone());
+        }
+
+        steps
+    }
 
-                    if let Some(tail_delta) = Self::get_tail_delta(head, &knot) {
-                        knot = knot.add_delta(tail_delta);
+    fn step(&mut self, delta:(i32,i32)) {
+        self.steps += 1;
+        self.knots[0] = self.knots[0].add_delta(delta);
+        self.visit(0);
 
-                        if tail_index == self.knots.len() {
-                            // Is last tail?
-                            tail_moves.push(knot.clone());
-                        }
-                    }
-                }
+        for index in 1..self.knots.len() {
+            let head = self.knots[index - 1];
+            let knot = self.knots[index];
 
-                self.knots.push_back(knot);
+            if head.get_dist(&knot) <= 1 {
+                // Knots further down won't move either
+                break;
             }
+
+            self.knots[index] = knot.add_delta(((head.x - knot.x).signum(), (head.y - knot.y).signum()));
+            self.visit(index);
         }
+    }
+
+    fn visit(&mut self, knot:usize) {
+        let step = self.steps;
+        self.visits[knot].entry(self.knots[knot])
+            .and_modify(|visit| visit.count += 1)
+            .or_insert(Visit{count:1, first_step:step});
+    }
+
+    fn visit_counts(&self) -> Vec<usize> {
+        self.visits.iter().map(|visits| visits.len()).collect()
+    }
 
-        tail_moves
+    #[allow(dead_code)]
+    fn stats(&self, knot:usize) -> VisitStats {
+        let visits = self.visits[knot].clone();
+        let min = visits.keys().fold(Pos::initial(), |acc, pos| Pos{x:acc.x.min(pos.x), y:acc.y.min(pos.y)});
+        let max = visits.keys().fold(Pos::initial(), |acc, pos| Pos{x:acc.x.max(pos.x), y:acc.y.max(pos.y)});
+        VisitStats{visits, min, max}
     }
 
-    fn move_head(head: &mut Pos, dir:char) {
-        let delta_pos = match dir {
-            'R' => (1,0),
-            'L' => (-1,0),
-            'U' => (0,-1),
-            'D' => (0,1),
-            _ => panic!(""),
-        };
+    fn bounds(&self) -> (Pos, Pos) {
+        let all = self.visits.iter().flat_map(|visits| visits.keys()).chain(self.knots.iter());
+        let min = all.clone().fold(Pos::initial(), |acc, pos| Pos{x:acc.x.min(pos.x), y:acc.y.min(pos.y)});
+        let max = all.fold(Pos::initial(), |acc, pos| Pos{x:acc.x.max(pos.x), y:acc.y.max(pos.y)});
+        (min, max)
+    }
+
+    fn render_frame(&self, symbol:impl Fn(&Pos) -> char) -> String {
+        let (min, max) = self.bounds();
 
-        // move header
-        *head = head.add_delta(delta_pos);
+        (min.y..=max.y)
+            .map(|y| (min.x..=max.x).map(|x| symbol(&Pos{x,y})).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
-    fn get_tail_delta(head:&Pos, tail:&Pos) -> Option<(i32, i32)> {
-        if head.get_dist(tail) > 1 {
-            // Only move tail if distance is greater than 1
-            let deltas = if head.x == tail.x || head.y == tail.y {
-                [(1,0),(-1,0),(0,1),(0,-1)]
-            } else {
-                [(-1,-1),(-1,1),(1,-1),(1,1)]
-            };
+    #[allow(dead_code)]
+    fn render(&self) -> String {
+        self.render_frame(|pos| match self.knots.iter().position(|knot| knot == pos) {
+            Some(0) => 'H',
+            Some(index) => std::char::from_digit(index as u32 % 36, 36).unwrap(),
+            None if *pos == Pos::initial() => 's',
+            None => '.',
+        })
+    }
 
-            let delta = deltas.iter().find( |&delta| tail.add_delta(*delta).get_dist(head) < 2 ).unwrap();
-            Some(*delta)
+    #[allow(dead_code)]
+    fn render_visited(&self, knot:usize) -> String {
+        self.render_frame(|pos| if *pos == Pos::initial() {
+            's'
+        } else if self.visits[knot].contains_key(pos) {
+            '#'
         } else {
-            None
-        }
+            '.'
+        })
+    }
+}
+
+#[allow(dead_code)]
+impl VisitStats {
+    fn heatmap(&self) -> Vec<Vec<usize>> {
+        (self.min.y..=self.max.y)
+            .map(|y| (self.min.x..=self.max.x)
+                .map(|x| self.visits.get(&Pos{x,y}).map_or(0, |visit| visit.count))
+                .collect())
+            .collect()
+    }
+
+    fn first_visit(&self, pos:&Pos) -> Option<usize> {
+        self.visits.get(pos).map(|visit| visit.first_step)
+    }
+
+    // Brighter cells were visited more often
+    fn to_pgm(&self) -> String {
+        let heatmap = self.heatmap();
+        let max_count = heatmap.iter().flatten().copied().max().unwrap_or(0).clamp(1, 65535);
+        to_pgm(&heatmap, max_count)
     }
 }
 
 fn part1(input : String) -> String {
     let mut rope = Rope::new(2);
-    let tail_moves = rope.process_moves(input).iter().copied().collect::<HashSet<Pos>>();
-    tail_moves.len().to_string()
+    rope.process_moves(input);
+    rope.visit_counts()[1].to_string()
 }
 
 
 fn part2(input : String) -> String {
     let mut rope = Rope::new(10);
-    let tail_moves = rope.process_moves(input).iter().copied().collect::<HashSet<Pos>>();
-    tail_moves.len().to_string()}
+    rope.process_moves(input);
+    rope.visit_counts()[9].to_string()
+}
 
 
 #[cfg(test)]
@@ -185,4 +267,71 @@ U 20";
 
         assert_eq!("2734", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_render() {
+        let mut rope = Rope::new(10);
+        let steps = rope.process_moves("R 5\nU 8".to_string());
+
+        assert_eq!(13, steps.len());
+        assert_eq!(Pos{x:4, y:0}, steps[4][1]);
+        assert_eq!(vec![14, 12, 10, 8, 6, 5, 4, 3, 2, 1], rope.visit_counts());
+        assert_eq!(".....H
+.....1
+.....2
+.....3
+....54
+...6..
+..7...
+.8....
+9.....", rope.render());
+        assert_eq!("......
+......
+......
+......
+......
+...#..
+..#...
+.#....
+s.....", rope.render_visited(6));
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let mut rope = Rope::new(3);
+        rope.process_moves("UR 3\nV -4 1".to_string());
+
+        assert_eq!(vec![Pos{x:-1, y:-2}, Pos{x:0, y:-2}, Pos{x:1, y:-1}], rope.knots);
+        assert_eq!(vec![7, 5, 2], rope.visit_counts());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut rope = Rope::new(2);
+        rope.process_moves("R 4
+U 4
+L 3
+D 1
+R 4
+D 1
+L 5
+R 2".to_string());
+
+        let stats = rope.stats(1);
+
+        assert_eq!((Pos{x:0, y:-4}, Pos{x:4, y:0}), (stats.min, stats.max));
+        assert_eq!(Some(0), stats.first_visit(&Pos::initial()));
+        assert_eq!(Some(2), stats.first_visit(&Pos{x:1, y:0}));
+        assert_eq!(None, stats.first_visit(&Pos{x:0, y:-4}));
+        assert_eq!(13, stats.heatmap().iter().flatten().filter(|&&count| count > 0).count());
+        assert_eq!("P2
+5 5
+2
+0 0 1 1 0
+0 0 0 1 2
+0 1 1 1 1
+0 0 0 0 1
+1 1 1 1 0
+", stats.to_pgm());
+    }
 }
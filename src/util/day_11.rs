@@ -1,32 +1,141 @@
 use std::collections::VecDeque;
-use super::Part;
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
-    let monkeys = input.split("\n\n").into_iter()
-        .map(|monkey_str| monkey_str.lines()
-            .map(|line| line.trim())
-            .collect::<Vec<&str>>())
-        .collect::<Vec<Vec<&str>>>();
+pub fn params(part:Part) -> Vec<Param> {
+    let (rounds, relief) = match part {
+        Part::Part1 => (20, "old / 3"),
+        Part::Part2 => (10_000, "old"),
+    };
 
-    match part {
-        Part::Part1 => part1(monkeys),
-        Part::Part2 => part2(monkeys)
-    }
+    vec![
+        Param::ranged("rounds", "Number of rounds", rounds, 0..=i64::MAX),
+        Param::text("relief", "Worry level once an item was inspected without damage, as an operation on old", relief),
+    ]
+}
+
+pub fn solve_with_params(input : String, _part: Part, params:&Params) -> Result<String, String> {
+    run_monkey_business(&input, params.int("rounds") as usize, params.text("relief"))
+        .map(|monkey_business| monkey_business.to_string())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
 }
 
+// Right hand side of "new = ...", operands are old or constants
+#[derive(Debug, Clone, PartialEq)]
 enum Operation {
-    Add(u64),
-    Multiply(u64),
-    Square,
+    Old,
+    Constant(u64),
+    Binary(Operator, Box<Operation>, Box<Operation>),
+}
+
+impl Operator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+        }
+    }
+
+    fn parse(token:&str) -> Result<Operator, String> {
+        match token {
+            "+" => Ok(Operator::Add),
+            "-" => Ok(Operator::Subtract),
+            "*" => Ok(Operator::Multiply),
+            "/" => Ok(Operator::Divide),
+            _ => Err(format!("unsupported operator '{}'", token)),
+        }
+    }
 }
 
 impl Operation {
-    fn do_op(&self, old:u64) -> u64 {
-        match &self {
-            Operation::Add(value) => old + value,
-            Operation::Multiply(value) => old * value,
-            Operation::Square => old * old,
+    // Operands and operators separated by spaces, * and / bind before + and -
+    fn parse(input:&str) -> Result<Operation, String> {
+        let tokens = input.split_whitespace().collect::<Vec<_>>();
+
+        if tokens.is_empty() {
+            return Err("empty operation".to_string());
+        }
+        if tokens.len() % 2 == 0 {
+            return Err(format!("operation ends with operator '{}'", tokens[tokens.len() - 1]));
+        }
+
+        let operands = tokens.iter().step_by(2)
+            .map(|token| match *token {
+                "old" => Ok(Operation::Old),
+                _ => token.parse::<u64>().map(Operation::Constant).map_err(|_| format!("unsupported operand '{}'", token)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let operators = tokens.iter().skip(1).step_by(2)
+            .map(|token| Operator::parse(token))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Products are combined as they come, sums once all products are known
+        let mut terms = vec![operands[0].clone()];
+        let mut sum_operators = vec![];
+
+        for (operator, operand) in operators.into_iter().zip(operands.into_iter().skip(1)) {
+            if operator == Operator::Divide && operand == Operation::Constant(0) {
+                return Err("division by zero".to_string());
+            }
+
+            match operator {
+                Operator::Multiply | Operator::Divide => {
+                    let left = terms.pop().unwrap();
+                    terms.push(Operation::Binary(operator, Box::new(left), Box::new(operand)));
+                },
+                Operator::Add | Operator::Subtract => {
+                    sum_operators.push(operator);
+                    terms.push(operand);
+                },
+            }
+        }
+
+        let mut terms = terms.into_iter();
+        let first = terms.next().unwrap();
+        Ok(sum_operators.into_iter().zip(terms)
+            .fold(first, |left, (operator, right)| Operation::Binary(operator, Box::new(left), Box::new(right))))
+    }
+
+    fn uses(&self, operator:Operator) -> bool {
+        match self {
+            Operation::Binary(used, left, right) => *used == operator || left.uses(operator) || right.uses(operator),
+            _ => false,
+        }
+    }
+
+    // With a modulus every value is kept below it, otherwise running out of u64 is an error
+    fn do_op(&self, old:u64, modulus:Option<u64>) -> Result<u64, String> {
+        let reduce = |value:u64| modulus.map_or(value, |modulus| value % modulus);
+
+        match self {
+            Operation::Old => Ok(reduce(old)),
+            Operation::Constant(value) => Ok(reduce(*value)),
+            Operation::Binary(operator, left, right) => {
+                let (left, right) = (left.do_op(old, modulus)?, right.do_op(old, modulus)?);
+                let value = match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract if left < right => return Err(format!("worry level {} - {} drops below zero", left, right)),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                    Operator::Divide if right == 0 => return Err(format!("worry level {} / 0", left)),
+                    Operator::Divide => left.checked_div(right),
+                };
+
+                value.map(reduce).ok_or(format!("worry level overflow in {} {} {}", left, operator.symbol(), right))
+            }
         }
     }
 }
@@ -40,46 +149,45 @@ struct Monkey {
     inspect_count:usize,
 }
 
-impl Monkey {
-    fn new(input:&Vec<&str>) -> Monkey {
-
-        let items = input[1].to_string()[16..].split([' ',','])
-            .into_iter().filter(|item| item.len() > 0)
-            .map(|item| item.parse::<u64>().unwrap())
-            .collect::<VecDeque<_>>();
-
-        let operation_str = input[2].to_string()[17..].to_string().split(' ')
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
-
-        let test = input[3].split(' ').last().unwrap().parse::<u64>().unwrap();
-        let next_monkey1 = input[4].split(' ').last().unwrap().parse::<usize>().unwrap();
-        let next_monkey2 = input[5].split(' ').last().unwrap().parse::<usize>().unwrap();
-
-        let operation = if operation_str[0].eq("old") && operation_str[2].eq("old") {
-            Operation::Square
-        } else if operation_str[1].eq("*") {
-            Operation::Multiply(operation_str[2].parse::<u64>().unwrap())
-        } else {
-            Operation::Add(operation_str[2].parse::<u64>().unwrap())
-        };
+// All monkeys playing keep away together
+struct Troop {
+    monkeys:Vec<Monkey>,
+    relief:Operation,
+    // Least common multiple of the test divisors, None if something divides or subtracts worry levels
+    modulus:Option<u64>,
+}
 
-        Monkey{items, test_divisor: test, to_monkey_index_if_true: next_monkey1, to_monkey_index_if_false: next_monkey2, inspect_count:0, operation}
+impl Monkey {
+    fn new(input:&[&str]) -> Result<Monkey, String> {
+        // Rest of the line after its' expected start
+        let field = |index:usize, prefix:&str| input.get(index)
+            .and_then(|line| line.strip_prefix(prefix))
+            .ok_or(format!("expected '{}', got '{}'", prefix.trim_end(), input.get(index).unwrap_or(&"")));
+        let number = |text:&str| text.trim().parse::<u64>().map_err(|_| format!("invalid number '{}'", text.trim()));
+
+        let items = field(1, "Starting items:")?.split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(number)
+            .collect::<Result<VecDeque<_>, _>>()?;
+
+        let operation_str = field(2, "Operation: new = ")?;
+        let operation = Operation::parse(operation_str)
+            .map_err(|error| format!("{} in '{}'", error, operation_str))?;
+
+        let test = number(field(3, "Test: divisible by ")?)?;
+        let next_monkey1 = number(field(4, "If true: throw to monkey ")?)? as usize;
+        let next_monkey2 = number(field(5, "If false: throw to monkey ")?)? as usize;
+
+        Ok(Monkey{items, test_divisor: test, to_monkey_index_if_true: next_monkey1, to_monkey_index_if_false: next_monkey2, inspect_count:0, operation})
     }
 
-    fn do_turn(&mut self, part_two:bool, divisor:u64) -> Vec<(usize,u64)> {
+    fn do_turn(&mut self, relief:&Operation, modulus:Option<u64>) -> Result<Vec<(usize,u64)>, String> {
         let mut output = vec![];
         self.inspect_count += self.items.len();
 
         while self.items.len() > 0 {
             let item = self.items.pop_front().unwrap();
-            let mut next_value = self.operation.do_op(item);
-            if !part_two {
-                next_value = next_value / 3;
-            } else {
-                next_value = next_value % divisor;
-            }
+            let next_value = relief.do_op(self.operation.do_op(item, modulus)?, modulus)?;
 
             if next_value % self.test_divisor == 0 {
                 output.push((self.to_monkey_index_if_true, next_value));
@@ -88,33 +196,82 @@ impl Monkey {
             }
         }
 
-        output
+        Ok(output)
     }
 }
 
-fn run_monkey_business(input: Vec<Vec<&str>>, rounds:usize, part2:bool) -> String {
-    let mut monkeys = input.iter().map(|item| Monkey::new(item)).collect::<Vec<_>>();
-    let divisor:u64 = monkeys.iter().fold(1, |acc, m  | acc * m.test_divisor);
+fn gcd(a:u64, b:u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
 
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            let next_items = monkeys[i].do_turn(part2, divisor);
+impl Troop {
+    fn new(input:&str, relief:&str) -> Result<Troop, String> {
+        let monkeys = input.split("\n\n")
+            .enumerate()
+            .map(|(index, monkey_str)| {
+                let lines = monkey_str.lines().map(|line| line.trim()).collect::<Vec<&str>>();
+                Monkey::new(&lines).map_err(|error| format!("monkey {}: {}", index, error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (index, monkey) in monkeys.iter().enumerate() {
+            if monkey.test_divisor == 0 {
+                return Err(format!("monkey {}: test divides by zero", index));
+            }
+            if let Some(target) = [monkey.to_monkey_index_if_true, monkey.to_monkey_index_if_false].iter().find(|&&target| target >= monkeys.len()) {
+                return Err(format!("monkey {}: throws to unknown monkey {}", index, target));
+            }
+        }
+
+        let relief = Operation::parse(relief).map_err(|error| format!("relief: {} in '{}'", error, relief))?;
+
+        // Remainders of the divisibility tests survive reducing by the LCM, as long as nothing is divided.
+        // Reduced values can't tell when subtracting drops below zero, so that rules it out as well.
+        let operations = monkeys.iter().map(|monkey| &monkey.operation).chain([&relief]).collect::<Vec<_>>();
+        let modulus = if operations.iter().any(|operation| operation.uses(Operator::Divide) || operation.uses(Operator::Subtract)) {
+            None
+        } else {
+            monkeys.iter().try_fold(1_u64, |acc, monkey| (acc / gcd(acc, monkey.test_divisor)).checked_mul(monkey.test_divisor))
+        };
+
+        Ok(Troop{monkeys, relief, modulus})
+    }
+
+    // Items held by every monkey after the round. On error the round is left unfinished.
+    fn play_round(&mut self) -> Result<Vec<Vec<u64>>, String> {
+        for i in 0..self.monkeys.len() {
+            let next_items = self.monkeys[i].do_turn(&self.relief, self.modulus)?;
             for (next_index, next_item) in next_items {
-                monkeys[next_index].items.push_back(next_item);
+                self.monkeys[next_index].items.push_back(next_item);
             }
         }
+
+        Ok(self.items())
     }
 
-    monkeys.sort_by( |a,b| b.inspect_count.cmp(&a.inspect_count) );
-    (monkeys[0].inspect_count * monkeys[1].inspect_count).to_string()
-}
+    fn items(&self) -> Vec<Vec<u64>> {
+        self.monkeys.iter().map(|monkey| monkey.items.iter().copied().collect()).collect()
+    }
+
+    fn inspection_counts(&self) -> Vec<usize> {
+        self.monkeys.iter().map(|monkey| monkey.inspect_count).collect()
+    }
 
-fn part1(input : Vec<Vec<&str>>) -> String {
-    run_monkey_business(input, 20, false)
+    fn monkey_business(&self) -> usize {
+        let mut counts = self.inspection_counts();
+        counts.sort_by(|a, b| b.cmp(a));
+        counts[0] * counts[1]
+    }
 }
 
-fn part2(input : Vec<Vec<&str>>) -> String {
-    run_monkey_business(input, 10_000, true)
+fn run_monkey_business(input:&str, rounds:usize, relief:&str) -> Result<usize, String> {
+    let mut troop = Troop::new(input, relief)?;
+
+    for round in 1..=rounds {
+        troop.play_round().map_err(|error| format!("round {}: {}", round, error))?;
+    }
+
+    Ok(troop.monkey_business())
 }
 
 #[cfg(test)]
@@ -216,6 +373,105 @@ Monkey 3:
         assert_eq!("12848882750", solve(input.to_string(), Part2));
     }
 
+    #[test]
+    fn test_operation() {
+        assert_eq!(Ok(Operation::Binary(Operator::Multiply, Box::new(Operation::Old), Box::new(Operation::Old))), Operation::parse("old * old"));
+        assert_eq!(Ok(Operation::Constant(7)), Operation::parse("7"));
+        assert_eq!(Ok(103), Operation::parse("old * old + 3").and_then(|op| op.do_op(10, None)));
+        assert_eq!(Ok(26), Operation::parse("3 + old * 5 + old * old / 4 - 8").and_then(|op| op.do_op(5, None)));
+        assert_eq!(Ok(2), Operation::parse("old - 9 - 4").and_then(|op| op.do_op(15, None)));
+        assert_eq!(Ok(1), Operation::parse("old / 2 / 3").and_then(|op| op.do_op(7, None)));
+        assert_eq!(Err("worry level 5 - 9 drops below zero".to_string()), Operation::parse("old - 9").and_then(|op| op.do_op(5, None)));
+        assert_eq!(Err("worry level 0 / 0".to_string()), Operation::parse("old / old").and_then(|op| op.do_op(0, None)));
+
+        assert_eq!(Err("empty operation".to_string()), Operation::parse(" "));
+        assert_eq!(Err("operation ends with operator '*'".to_string()), Operation::parse("old *"));
+        assert_eq!(Err("unsupported operator '^'".to_string()), Operation::parse("old ^ 2"));
+        assert_eq!(Err("unsupported operand '(old'".to_string()), Operation::parse("(old + 1) * 2"));
+        assert_eq!(Err("unsupported operand 'new'".to_string()), Operation::parse("new + 1"));
+        assert_eq!(Err("division by zero".to_string()), Operation::parse("old / 0"));
+
+        let input = include_str!("../../input/input_11.txt").replacen("old * old", "old ** old", 1);
+        assert_eq!(Some("monkey 6: unsupported operator '**' in 'old ** old'".to_string()), Troop::new(&input, "old / 3").err());
+        assert_eq!(Some("relief: unsupported operand 'worry' in 'worry / 3'".to_string()), Troop::new(include_str!("../../input/input_11.txt"), "worry / 3").err());
+
+        let input = include_str!("../../input/input_11.txt");
+        assert_eq!(Some("monkey 0: test divides by zero".to_string()), Troop::new(&input.replacen("divisible by 19", "divisible by 0", 1), "old").err());
+        assert_eq!(Some("monkey 0: throws to unknown monkey 9".to_string()), Troop::new(&input.replacen("throw to monkey 6", "throw to monkey 9", 1), "old").err());
+        assert_eq!(Some("monkey 0: invalid number 'x'".to_string()), Troop::new(&input.replacen("Starting items: ", "Starting items: x, ", 1), "old").err());
+        assert_eq!(Some("monkey 0: expected 'Test: divisible by', got 'Test: odd'".to_string()),
+                   Troop::new(&input.replacen("Test: divisible by 19", "Test: odd", 1), "old").err());
+        assert_eq!(Some("monkey 0: expected 'If true: throw to monkey', got ''".to_string()), Troop::new(input.split("    If true").next().unwrap(), "old").err());
+    }
 
+    #[test]
+    fn test_troop() {
+        let input = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
 
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+        let mut troop = Troop::new(input, "old / 3").unwrap();
+        assert_eq!(vec![vec![79, 98], vec![54, 65, 75, 74], vec![79, 60, 97], vec![74]], troop.items());
+        assert_eq!(Ok(vec![vec![20, 23, 27, 26], vec![2080, 25, 167, 207, 401, 1046], vec![], vec![]]), troop.play_round());
+        assert_eq!(Ok(vec![vec![695, 10, 71, 135, 350], vec![43, 49, 58, 55, 362], vec![], vec![]]), troop.play_round());
+
+        for _ in 2..20 {
+            troop.play_round().unwrap();
+        }
+        assert_eq!(vec![vec![10, 12, 14, 26, 34], vec![245, 93, 53, 199, 115], vec![], vec![]], troop.items());
+        assert_eq!(vec![101, 95, 7, 105], troop.inspection_counts());
+        assert_eq!(10605, troop.monkey_business());
+
+        let mut troop = Troop::new(input, "old").unwrap();
+        assert_eq!(Some(96577), troop.modulus);
+        troop.play_round().unwrap();
+        assert_eq!(vec![2, 4, 3, 6], troop.inspection_counts());
+    }
+
+    #[test]
+    fn test_worry_management() {
+        let input = include_str!("../../input/input_11.txt");
+        let relief = |relief:&str| Params::parse(&params(Part2), &[&format!("relief={}", relief)]).unwrap();
+
+        // Dividing by one keeps worry levels intact but rules out the modulus
+        assert_eq!(None, Troop::new(input, "old / 1").unwrap().modulus);
+        let error = solve_with_params(input.to_string(), Part2, &relief("old / 1")).unwrap_err();
+        assert!(error.starts_with("round "));
+        assert!(error.contains(": worry level overflow in "));
+
+        // Same as the default, any relief without division or subtraction is reduced by the modulus
+        assert_eq!(Ok("13606755504".to_string()), solve_with_params(input.to_string(), Part2, &relief("old * 1")));
+        assert_eq!(None, Troop::new(input, "old + 1 - 1").unwrap().modulus);
+        assert_eq!(Ok("54752".to_string()), solve_with_params(input.to_string(), Part1, &Params::parse(&params(Part1), &["relief=old / 3"]).unwrap()));
+
+        // Dropping below zero is an error with and without relief
+        let input = input.replacen("old * old", "old - 100", 1);
+        assert_eq!(Err("round 1: worry level 99 - 100 drops below zero".to_string()), solve_with_params(input.to_string(), Part1, &relief("old / 3")));
+        assert_eq!(None, Troop::new(&input, "old").unwrap().modulus);
+        assert_eq!(Err("worry level 99 - 100 drops below zero".to_string()), Troop::new(&input, "old").unwrap().play_round().map(|_| ()));
+    }
 }
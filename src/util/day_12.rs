@@ -22,14 +22,42 @@ impl Pos {
     }
 }
 
+struct ClimbingRules {
+    max_ascent:u8,
+    // Unlimited if None
+    max_descent:Option<u8>,
+    diagonal:bool,
+    // Cost of a single step given the height difference, destination - current
+    step_cost:fn(i32) -> usize,
+}
+
 struct Map {
     map:Vec<Vec<char>>,
     height:usize,
     width:usize,
+    rules:ClimbingRules,
+}
+
+impl ClimbingRules {
+    fn standard() -> ClimbingRules {
+        ClimbingRules{max_ascent:1, max_descent:None, diagonal:false, step_cost:|_| 1}
+    }
+
+    fn allows(&self, current_height:u8, dest_height:u8) -> bool {
+        if dest_height > current_height {
+            dest_height - current_height <= self.max_ascent
+        } else {
+            self.max_descent.is_none_or(|max_descent| current_height - dest_height <= max_descent)
+        }
+    }
 }
 
 impl Map {
     fn new(input:String) -> Map {
+        Self::with_rules(input, ClimbingRules::standard())
+    }
+
+    fn with_rules(input:String, rules:ClimbingRules) -> Map {
         let map = input.lines()
             .map(|line| line.chars()
                 .collect())
@@ -38,7 +66,7 @@ impl Map {
         let height = map.len();
         let width = map.first().unwrap().len();
 
-        Map{map, height, width}
+        Map{map, height, width, rules}
     }
 
     fn get_height(&self, pos:&Pos) -> u8 {
@@ -53,12 +81,7 @@ impl Map {
     }
 
 
-    fn is_end(&self, pos:&Pos) -> bool {
-        *self.map.get(pos.y).unwrap().get(pos.x).unwrap() as char == 'E'
-    }
-
-    fn get_moves(&self, current:&Pos) -> Vec<Pos> {
-        let current_height = self.get_height(&current);
+    fn neighbours(&self, current:&Pos) -> Vec<Pos> {
         let mut moves = vec![];
 
         if current.x > 0 {
@@ -74,74 +97,132 @@ impl Map {
             moves.push(Pos::new(current.x,current.y+1));
         }
 
-        moves.iter()
-            .map(|p| (p, self.get_height(p)))
-            .filter(|(_, dest_height)| *dest_height <= current_height || *dest_height == current_height + 1)
-            .map(|(p,_)| *p)
-            .collect()
+        if self.rules.diagonal {
+            let diagonals = moves.iter()
+                .filter(|p| p.x != current.x)
+                .flat_map(|p| moves.iter()
+                    .filter(|q| q.y != current.y)
+                    .map(move |q| Pos::new(p.x, q.y)))
+                .collect::<Vec<_>>();
+            moves.extend(diagonals);
+        }
+
+        moves
     }
 
+    fn get_moves(&self, current:&Pos) -> Vec<Pos> {
+        let current_height = self.get_height(current);
 
-    fn invert(&mut self) -> &mut Map {
-        self.map.iter_mut()
-            .for_each(| row| row.iter_mut()
-                .for_each(|ch| {
-                    let next_char = match *ch {
-                        'E' => 'S',
-                        'S' => 'a',
-                        'a' => 'E',
-                        _ => ('z' as u8 - (*ch as u8 - 'a' as u8)) as char
-                    };
-                    *ch = next_char;
-                }));
-        self
+        self.neighbours(current).iter()
+            .map(|p| (p, self.get_height(p)))
+            .filter(|(_, dest_height)| self.rules.allows(current_height, *dest_height))
+            .map(|(p,_)| *p)
+            .collect()
     }
 
-    fn find_end(&self) -> Option<usize> {
-        let start = self.map.iter().enumerate()
+    fn find(&self, target:char) -> Pos {
+        self.map.iter().enumerate()
             .flat_map(|(y,row)| row.iter().enumerate()
                 .map(move |(x,ch)| (Pos::new(x,y),ch)))
-            .find(|(_, &ch)| ch == 'S').unwrap().0;
+            .find(|(_, &ch)| ch == target).unwrap().0
+    }
 
-        let mut visited:HashMap<Pos, usize> = HashMap::new();
+    fn step_cost(&self, from:&Pos, to:&Pos) -> usize {
+        (self.rules.step_cost)(self.get_height(to) as i32 - self.get_height(from) as i32)
+    }
+
+    // Lowest cost and previous position for every position reachable from source. When reversed the
+    // search follows moves backwards, giving the cost from every position to source instead.
+    fn shortest_paths(&self, source:Pos, reverse:bool) -> HashMap<Pos, (usize, Pos)> {
+        let mut visited:HashMap<Pos, (usize, Pos)> = HashMap::new();
         let mut queue = BinaryHeap::new();
 
         // Add starting position
-        queue.push(Reverse((0, start)));
-
-        while !queue.is_empty() {
-            let (current_distance, current) = queue.pop().unwrap().0;
-            let prev_distance_opt = visited.get(&current);
+        queue.push(Reverse((0, source, source)));
 
-            // Did we reach the end
-            if self.is_end(&current) {
-                return Some(current_distance as usize);
+        while let Some(Reverse((current_distance, current, prev))) = queue.pop() {
+            if visited.contains_key(&current) {
+                continue;
             }
 
-            if prev_distance_opt.is_some() && *prev_distance_opt.unwrap() <= current_distance {
-                continue;
+            visited.insert(current, (current_distance, prev));
+
+            let next_moves = if reverse {
+                self.get_reverse_moves(&current)
+            } else {
+                self.get_moves(&current)
+            };
+
+            for next_move in next_moves.into_iter().filter(|p| !visited.contains_key(p)) {
+                let cost = if reverse {
+                    self.step_cost(&next_move, &current)
+                } else {
+                    self.step_cost(&current, &next_move)
+                };
+                queue.push(Reverse((current_distance + cost, next_move, current)));
             }
+        }
+
+        visited
+    }
+
+    // Positions from which current can be reached in one move
+    fn get_reverse_moves(&self, current:&Pos) -> Vec<Pos> {
+        self.neighbours(current).into_iter()
+            .filter(|p| self.get_moves(p).contains(current))
+            .collect()
+    }
 
-            visited.insert( current, current_distance);
-
-            let next_dist = current_distance + 1;
-            self.get_moves(&current).iter()
-                .filter(|&p| {
-                    if let Some(prev_dist) = visited.get(p) {
-                        // Previous visit to this node was a longer path?
-                        *prev_dist > next_dist
-                    } else {
-                        // Not visited, add to queue
-                        true
-                    }
-                })
-                .copied()
-                .for_each(|next_move|
-                    queue.push(Reverse((next_dist, next_move))));
+    #[allow(dead_code)]
+    fn find_path(&self) -> Option<Vec<Pos>> {
+        let (start, end) = (self.find('S'), self.find('E'));
+        let paths = self.shortest_paths(start, false);
+        paths.get(&end)?;
 
+        let mut path = vec![end];
+        while *path.last().unwrap() != start {
+            path.push(paths[path.last().unwrap()].1);
         }
 
-        None
+        path.reverse();
+        Some(path)
+    }
+
+    fn find_end(&self) -> Option<usize> {
+        self.shortest_paths(self.find('S'), false).get(&self.find('E')).map(|(cost, _)| *cost)
+    }
+
+    // Cost to reach the end from every position that can reach it
+    fn distances_to_end(&self) -> HashMap<Pos, usize> {
+        self.shortest_paths(self.find('E'), true).into_iter()
+            .map(|(pos, (distance, _))| (pos, distance))
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    fn render_path(&self, path:&[Pos]) -> String {
+        let mut output = vec![vec!['.'; self.width]; self.height];
+
+        for step in path.windows(2) {
+            let (from, to) = (step[0], step[1]);
+            output[from.y][from.x] = match (to.x as i32 - from.x as i32, to.y as i32 - from.y as i32) {
+                (1, 0) => '>',
+                (-1, 0) => '<',
+                (0, 1) => 'v',
+                (0, -1) => '^',
+                (1, -1) | (-1, 1) => '/',
+                _ => '\\',
+            };
+        }
+
+        if let Some(last) = path.last() {
+            output[last.y][last.x] = 'E';
+        }
+
+        output.iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
@@ -151,7 +232,11 @@ fn part1(input:String) -> String {
 }
 
 fn part2(input:String) -> String {
-    Map::new(input).invert().find_end().unwrap().to_string()
+    let map = Map::new(input);
+    map.distances_to_end().iter()
+        .filter(|(pos, _)| map.get_height(pos) == b'a')
+        .map(|(_, distance)| *distance)
+        .min().unwrap().to_string()
 }
 
 #[cfg(test)]
@@ -198,4 +283,59 @@ abdefghi";
 
         assert_eq!("522", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_path() {
+        let input = "Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+
+        let map = Map::new(input.to_string());
+        let path = map.find_path().unwrap();
+
+        assert_eq!(32, path.len());
+        assert_eq!("v..v<<<<
+>v.vv<<^
+.v.v>E^^
+.>v>>>^^
+..>>>>>^", map.render_path(&path));
+
+        let distances = map.distances_to_end();
+        assert_eq!(Some(&31), distances.get(&Pos::new(0, 0)));
+        assert_eq!(Some(&0), distances.get(&Pos::new(5, 2)));
+        assert_eq!(Some(&29), distances.get(&Pos::new(0, 4)));
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = "Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+
+        // Unit cost with diagonal moves
+        let rules = ClimbingRules{max_ascent:1, max_descent:None, diagonal:true, step_cost:|_| 1};
+        let map = Map::with_rules(include_str!("../../input/input_12.txt").to_string(), rules);
+        assert_eq!(Some(373), map.find_end());
+
+        // Every change in height costs extra
+        let rules = ClimbingRules{max_ascent:1, max_descent:None, diagonal:false, step_cost:|diff| 1 + diff.unsigned_abs() as usize};
+        let map = Map::with_rules(include_str!("../../input/input_12.txt").to_string(), rules);
+        assert_eq!(Some(557), map.find_end());
+
+        // Climbing two steps at a time but never descending more than one
+        let rules = ClimbingRules{max_ascent:2, max_descent:Some(1), diagonal:false, step_cost:|_| 1};
+        let map = Map::with_rules(input.to_string(), rules);
+        assert_eq!(Some(27), map.find_end());
+
+        // Ascending is expensive, descending is free
+        let rules = ClimbingRules{max_ascent:25, max_descent:None, diagonal:false, step_cost:|diff| diff.max(0) as usize * 10};
+        let map = Map::with_rules(input.to_string(), rules);
+        assert_eq!(Some(250), map.find_end());
+        assert_eq!(Some(&0), map.distances_to_end().get(&Pos::new(5, 2)));
+        assert_eq!(Some(&250), map.distances_to_end().get(&Pos::new(0, 0)));
+    }
 }
@@ -1,5 +1,4 @@
 use std::collections::VecDeque;
-use std::mem::swap;
 use super::Part;
 
 pub fn solve(input : String, part: Part) -> String {
@@ -10,166 +9,151 @@ pub fn solve(input : String, part: Part) -> String {
     }
 }
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Floor {
+    // Sand falling below the lowest rock is lost
+    Abyss,
+    // Endless floor this many rows below the lowest rock
+    Solid(i32),
+}
+
+struct Cave {
+    // Rows from y = 0, columns from x = min_x, grown on demand
+    grid:Vec<VecDeque<char>>,
+    min_x:i32,
+    source:(i32,i32),
+    floor:Floor,
+    max_rock_y:i32,
+}
 
-fn parse(line:&str) -> Vec<(usize,usize)> {
+fn parse(line:&str) -> Vec<(i32,i32)> {
     line.split(" -> ")
         .map(|item| {
-            let mut it = item.split(',').into_iter();
-            let x = it.next().unwrap().parse::<usize>().unwrap();
-            let y = it.next().unwrap().parse::<usize>().unwrap();
+            let mut it = item.split(',');
+            let x = it.next().unwrap().parse::<i32>().unwrap();
+            let y = it.next().unwrap().parse::<i32>().unwrap();
             (x,y)
         })
         .collect::<Vec<_>>()
 }
 
-fn build_map(positions:Vec<Vec<(usize,usize)>>) -> ((usize, usize),Vec<Vec<char>>) {
-    let max_y = positions.iter().flat_map(|line| line.iter()).map(|(_,y)| *y).max().unwrap();
-    let max_x = positions.iter().flat_map(|line| line.iter()).map(|(x,_)| *x).max().unwrap();
-    let min_x = positions.iter().flat_map(|line| line.iter()).map(|(x,_)| *x).min().unwrap();
-
-    let width = max_x - min_x + 3;
-    let height = max_y + 2;
-
-    let mut normalized_positions = positions.iter()
-        .map(|line| line.iter()
-            .map(|(x,y)| (*x - min_x+1, *y)).collect::<VecDeque<_>>())
-        .collect::<VecDeque<_>>();
-
-    let mut map = vec![];
-    for _ in 0..height {
-        let mut line = vec![];
-        for _ in 0..width {
-            line.push('.');
-        }
-        map.push(line);
-    }
-
-    while !normalized_positions.is_empty() {
-        let mut lines = normalized_positions.pop_front().unwrap();
-
-        while lines.len() > 1 {
-            let (mut x0, mut y0) = lines.pop_front().unwrap();
-            let (mut x1, mut y1) = *lines.front().unwrap();
-
-            if x0 > x1 {
-                swap(&mut x0, &mut x1);
-            }
-
-            if y0 > y1 {
-                swap(&mut y0, &mut y1);
-            }
-
-            for y in y0..=y1 {
-                for x in x0..=x1 {
-                    //println!("{},{}",x,y);
-                    *map.get_mut(y).unwrap().get_mut(x).unwrap() = '#';
+impl Cave {
+    fn new(input:&str, source:(i32,i32), floor:Floor) -> Cave {
+        let paths = input.lines().map(parse).collect::<Vec<_>>();
+        let max_rock_y = paths.iter().flatten().map(|(_,y)| *y).max().unwrap();
+        let height = match floor {
+            Floor::Abyss => max_rock_y + 1,
+            Floor::Solid(offset) => max_rock_y + offset + 1,
+        };
+
+        let mut cave = Cave{grid:vec![VecDeque::from(vec!['.']); height.max(source.1 + 1) as usize], min_x:source.0, source, floor, max_rock_y};
+
+        for path in paths.iter() {
+            for line in path.windows(2) {
+                let ((x0, y0), (x1, y1)) = (line[0], line[1]);
+                for y in y0.min(y1)..=y0.max(y1) {
+                    for x in x0.min(x1)..=x0.max(x1) {
+                        cave.set((x,y), '#');
+                    }
                 }
             }
         }
+
+        cave
     }
 
-    ((500 - min_x + 1, 0), map)
-}
+    fn max_x(&self) -> i32 {
+        self.min_x + self.grid[0].len() as i32 - 1
+    }
 
-fn drop_sand(pos:(usize,usize), map:&mut Vec<Vec<char>>, part_two:bool) -> bool {
-    let mut current_pos = pos;
-    let height = map.len();
-    let width = map.first().unwrap().len();
+    fn ensure_column(&mut self, x:i32) {
+        while x < self.min_x {
+            self.grid.iter_mut().for_each(|row| row.push_front('.'));
+            self.min_x -= 1;
+        }
 
-    loop {
-        let (x,y) = current_pos;
-        let mut next = vec![];
+        while x > self.max_x() {
+            self.grid.iter_mut().for_each(|row| row.push_back('.'));
+        }
+    }
 
-        // Try down
-        if y == height - 1 {
-            if !part_two {
-                return true;
+    fn get(&self, (x,y):(i32,i32)) -> char {
+        if let Floor::Solid(offset) = self.floor {
+            if y == self.max_rock_y + offset {
+                return '#';
             }
-        } else {
-            next.push((x,y+1));
         }
 
-        // try left-down
-        if x > 0 && y < height - 1 {
-            next.push((x-1,y+1))
+        if x < self.min_x || x > self.max_x() {
+            '.'
+        } else {
+            self.grid[y as usize][(x - self.min_x) as usize]
         }
+    }
 
-        // try down-right
-        if x < width - 1 && y < height - 1 {
-            next.push((x+1,y+1))
+    fn set(&mut self, (x,y):(i32,i32), item:char) {
+        self.ensure_column(x);
+        let min_x = self.min_x;
+        self.grid[y as usize][(x - min_x) as usize] = item;
+    }
+
+    // Drops one grain of sand, returns where it came to rest. None if it fell into the abyss
+    // or the source is blocked.
+    fn drop_grain(&mut self) -> Option<(i32,i32)> {
+        let (mut x, mut y) = self.source;
+        if self.get((x,y)) != '.' {
+            return None;
         }
 
-        if let Some(next_pos)  = next.iter().find(|&next_pos| get_pos(*next_pos, map) == '.') {
-            // Sand moved to next pos
-            current_pos = *next_pos;
-            continue;
-        } else {
-            // Sand stays
-            let current_item = map.get_mut(y).unwrap().get_mut(x).unwrap();
+        loop {
+            if self.floor == Floor::Abyss && y >= self.max_rock_y {
+                return None;
+            }
 
-            // Is the current spot occupied already?
-            if *current_item == '.' {
-                *current_item = 'o';
-                return false;
+            // Try down, left-down and down-right
+            if let Some(&next_pos) = [(x,y+1), (x-1,y+1), (x+1,y+1)].iter().find(|&&next_pos| self.get(next_pos) == '.') {
+                (x, y) = next_pos;
             } else {
-                return true;
+                self.set((x,y), 'o');
+                return Some((x,y));
             }
         }
     }
-}
 
-fn print(map:&Vec<Vec<char>>) {
-    for y in 0..map.len() {
-        let line = map.get(y).unwrap();
-        for x in 0..line.len() {
-            print!("{}", line.get(x).unwrap());
+    // Drops sand until it no longer comes to rest, returns the number of grains at rest
+    fn fill(&mut self) -> usize {
+        let mut grains = 0;
+        while self.drop_grain().is_some() {
+            grains += 1;
         }
-        println!();
+        grains
     }
-}
 
-fn get_pos(pos:(usize,usize), map:&Vec<Vec<char>> ) -> char {
-    let (x,y) = pos;
-    *map.get(y).unwrap().get(x).unwrap()
+    #[allow(dead_code)]
+    fn render(&self) -> String {
+        let height = match self.floor {
+            Floor::Abyss => self.grid.len() as i32,
+            Floor::Solid(offset) => self.max_rock_y + offset + 1,
+        };
+
+        (0..height)
+            .map(|y| (self.min_x..=self.max_x())
+                .map(|x| if (x,y) == self.source && self.get((x,y)) == '.' { '+' } else { self.get((x,y)) })
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 fn part1(input : String) -> String {
-    let positions = input.lines().map(|line| parse(line)).collect::<Vec<_>>();
-    let (start_pos, mut map) = build_map(positions);
-
-    print(&map);
-    while !drop_sand(start_pos, &mut map, false) {}
-
-    print(&map);
-    map.iter().flat_map(|line| line.iter()).filter(|&ch| *ch == 'o').count().to_string()
+    Cave::new(&input, (500,0), Floor::Abyss).fill().to_string()
 }
 
 fn part2(input : String) -> String {
-    let positions = input.lines().map(|line| parse(line)).collect::<Vec<_>>();
-    let (start_pos, mut map) = build_map(positions);
-
-    while !drop_sand(start_pos, &mut map, true) {}
-
-    // Trim line with leading and trailing '.'
-    map.iter_mut().for_each(|line| {
-        while *line.last().unwrap() == '.' {
-            line.pop();
-        }
-
-        line.reverse();
-
-        while *line.last().unwrap() == '.' {
-            line.pop();
-        }
-    });
-
-    // Count non sand items within the area
-    let space_or_wall_items = map.iter().flat_map(|line| line.iter()).filter(|ch| **ch != 'o').count();
-
-    // Total area of triangle minus non sand items
-    (map.len() * map.len() - space_or_wall_items).to_string()
+    Cave::new(&input, (500,0), Floor::Solid(2)).fill().to_string()
 }
 
+
 #[cfg(test)]
 mod tests {
     use std::assert_eq;
@@ -209,4 +193,44 @@ mod tests {
 
         assert_eq!("22646", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_cave() {
+        let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+        let mut cave = Cave::new(input, (500,0), Floor::Abyss);
+
+        assert_eq!(Some((500,8)), cave.drop_grain());
+        assert_eq!(Some((499,8)), cave.drop_grain());
+        assert_eq!(22, cave.fill());
+        assert_eq!("......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.", cave.render());
+
+        let mut cave = Cave::new(input, (500,0), Floor::Solid(2));
+        assert_eq!(93, cave.fill());
+        assert_eq!(None, cave.drop_grain());
+        assert_eq!(Some('#'), cave.render().lines().last().unwrap().chars().next());
+        assert_eq!(21, cave.render().lines().next().unwrap().len());
+    }
+
+    #[test]
+    fn test_source() {
+        let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+        let mut cave = Cave::new(input, (497,3), Floor::Solid(1));
+
+        assert_eq!(Some((497,5)), cave.drop_grain());
+        assert_eq!(Some((495,8)), cave.drop_grain());
+        assert_eq!(Some((493,9)), cave.drop_grain());
+    }
 }
@@ -1,13 +1,36 @@
-use super::Part;
+use std::ops::RangeInclusive;
+use super::{Part, Param, Params, to_pgm};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
+
+pub fn params(part:Part) -> Vec<Param> {
+    match part {
+        Part::Part1 => vec![Param::ranged("row", "Row to count positions without a beacon in", 2000000, i32::MIN as i64..=i32::MAX as i64)],
+        Part::Part2 => vec![
+            Param::ranged("max_coord", "Upper bound of the x and y search area", 4000000, 0..=i32::MAX as i64),
+            Param::choice("search", "Algorithm used to find the distress beacon", &["boundary", "scan"]),
+        ],
+    }
+}
 
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
     match part {
-        Part::Part1 => part1(input, 2000000),
-        Part::Part2 => part2(input, 4000000)
+        Part::Part1 => Ok(part1(input, params.int("row") as i32)),
+        Part::Part2 => part2(input, params.int("max_coord") as i32, Search::parse(params.choice("search")))
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Search {
+    // Merge the sensor ranges of every row
+    RowScan,
+    // Only check the points just outside the sensor ranges where their boundaries cross
+    Boundary,
+}
+
 #[derive(Debug, Copy, Clone)]
 struct Sensor {
     position:Pos,
@@ -19,6 +42,14 @@ impl Sensor {
         Sensor{position:Pos{x: coord[0], y: coord[1]}, closest_beacon:Pos{x: coord[2], y: coord[3]}}
     }
 
+    fn radius(&self) -> u32 {
+        self.position.dist(&self.closest_beacon)
+    }
+
+    fn covers(&self, pos:&Pos) -> bool {
+        self.position.dist(pos) <= self.radius()
+    }
+
     fn get_range_for_y(&self, y:i32) -> Option<(i32,i32)> {
         let dist = self.position.dist(&self.closest_beacon);
         let y_dist = y.abs_diff(self.position.y);
@@ -38,6 +69,20 @@ struct Pos {
     y:i32,
 }
 
+struct Coverage {
+    sensors:Vec<Sensor>,
+}
+
+impl Search {
+    fn parse(name:&str) -> Search {
+        match name {
+            "scan" => Search::RowScan,
+            "boundary" => Search::Boundary,
+            _ => panic!("unknown search:{}", name),
+        }
+    }
+}
+
 impl Pos {
 
     fn dist(&self,other:&Pos) -> u32 {
@@ -45,6 +90,81 @@ impl Pos {
     }
 }
 
+impl Coverage {
+    fn new(input:&str) -> Coverage {
+        Coverage{sensors:input.lines().map(parse_line).collect()}
+    }
+
+    #[allow(dead_code)]
+    fn covered_by(&self, pos:&Pos) -> Option<&Sensor> {
+        self.sensors.iter().find(|sensor| sensor.covers(pos))
+    }
+
+    // Number of positions within range of a sensor, beacons included
+    fn covered_in_rows(&self, rows:RangeInclusive<i32>) -> usize {
+        rows.map(|y| {
+            let mut ranges_for_y_coord = self.sensors.iter()
+                .filter_map(|sensor| sensor.get_range_for_y(y))
+                .collect::<Vec<_>>();
+
+            ranges_for_y_coord.sort();
+            ranges_for_y_coord.reverse();
+
+            count_points_in_ranges(ranges_for_y_coord)
+        }).sum()
+    }
+
+    fn beacons(&self) -> Vec<Pos> {
+        let mut beacons = self.sensors.iter().map(|sensor| sensor.closest_beacon).collect::<Vec<_>>();
+        beacons.sort();
+        beacons.dedup();
+        beacons
+    }
+
+    // Smallest and largest position within range of any sensor
+    #[allow(dead_code)]
+    fn bounds(&self) -> (Pos, Pos) {
+        let min = self.sensors.iter()
+            .map(|sensor| Pos{x:sensor.position.x - sensor.radius() as i32, y:sensor.position.y - sensor.radius() as i32})
+            .fold(Pos{x:i32::MAX, y:i32::MAX}, |acc, pos| Pos{x:acc.x.min(pos.x), y:acc.y.min(pos.y)});
+        let max = self.sensors.iter()
+            .map(|sensor| Pos{x:sensor.position.x + sensor.radius() as i32, y:sensor.position.y + sensor.radius() as i32})
+            .fold(Pos{x:i32::MIN, y:i32::MIN}, |acc, pos| Pos{x:acc.x.max(pos.x), y:acc.y.max(pos.y)});
+        (min, max)
+    }
+
+    // PGM where every pixel is a scale x scale block sampled at its top left corner.
+    // Uncovered is black, covered grey, and blocks holding a beacon or sensor lighter and white.
+    #[allow(dead_code)]
+    fn to_pgm(&self, scale:u32) -> String {
+        let (min, max) = self.bounds();
+        let scale = scale.max(1) as i64;
+        let width = (max.x as i64 - min.x as i64 + 1 + scale - 1) / scale;
+        let height = (max.y as i64 - min.y as i64 + 1 + scale - 1) / scale;
+        let pixel = |pos:&Pos| ((pos.x as i64 - min.x as i64) / scale, (pos.y as i64 - min.y as i64) / scale);
+
+        let mut image = (0..height)
+            .map(|row| (0..width)
+                .map(|col| {
+                    let pos = Pos{x:(min.x as i64 + col * scale) as i32, y:(min.y as i64 + row * scale) as i32};
+                    if self.covered_by(&pos).is_some() { 1 } else { 0 }
+                })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        for beacon in self.beacons() {
+            let (col, row) = pixel(&beacon);
+            image[row as usize][col as usize] = 2;
+        }
+        for sensor in self.sensors.iter() {
+            let (col, row) = pixel(&sensor.position);
+            image[row as usize][col as usize] = 3;
+        }
+
+        to_pgm(&image, 3)
+    }
+}
+
 fn parse_line(line:&str) -> Sensor {
     let columns = line.split([' ',':',','])
         .filter(|col| !col.is_empty() && col.contains("="))
@@ -86,78 +206,115 @@ fn count_points_in_ranges(mut ranges:Vec<(i32, i32)>) -> usize {
     points as usize
 }
 
-fn find_non_overlap(y:i32, min_coord:i32, max_coord:i32, mut ranges:Vec<(i32, i32)>) -> Option<(i32, i32)> {
+// Uncovered x ranges of row y within 0..=max_coord
+fn find_gaps(max_coord:i32, mut ranges:Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    ranges.sort();
 
-    let mut last_interval = None;
+    let mut gaps = vec![];
+    // First x not known to be covered
+    let mut next_x = 0;
 
-    while !ranges.is_empty() {
-        let (min_x, max_x) = ranges.pop().unwrap();
-
-        if last_interval.is_some() {
-            let (_, last_max_x) = last_interval.unwrap();
-
-            // Is this range consumed by the last?
-            if last_max_x >= max_x {
-                continue;
-            }
-
-            // Is there a cap if at least 1?
-            if (min_x - last_max_x) > 1 && min_x > min_coord && min_x < max_coord {
-                // Found glitch
-                return Some((last_max_x + 1,y));
-            }
+    for (min_x, max_x) in ranges {
+        if min_x > next_x {
+            gaps.push((next_x, (min_x - 1).min(max_coord)));
         }
+        next_x = next_x.max(max_x + 1);
 
-        last_interval = Some((min_x, max_x));
+        if next_x > max_coord {
+            return gaps;
+        }
     }
 
-    None
+    gaps.push((next_x, max_coord));
+    gaps
 }
 
-fn part1(input : String, for_y:i32) -> String {
-    let sensors = input.lines().map(|line| parse_line(line)).collect::<Vec<_>>();
+// Stops after finding two, enough to tell the distress beacon is not unique
+fn scan_rows(sensors:&[Sensor], max_coord:i32) -> Vec<Pos> {
+    (0..=max_coord).flat_map(|y| {
+        let ranges_for_y_coord = sensors.iter()
+            .filter_map(|sensor| sensor.get_range_for_y(y))
+            .collect::<Vec<_>>();
 
-    let mut ranges_for_y_coord = sensors.iter()
-        .map(|sensor| sensor.get_range_for_y(for_y))
-        .filter(|range| range.is_some())
-        .map(|range| range.unwrap())
+        find_gaps(max_coord, ranges_for_y_coord).into_iter()
+            .flat_map(move |(min_x, max_x)| (min_x..=max_x).map(move |x| Pos{x,y}))
+    }).take(2).collect()
+}
+
+// Every corner of an uncovered region lies where two boundary lines just outside the sensor ranges
+// cross, or where such a line meets the edge of the search area, so only those points are checked.
+// Lines can lie outside the i32 range, so candidates are computed in i64.
+fn search_boundaries(sensors:&[Sensor], max_coord:i32) -> Vec<Pos> {
+    let max = max_coord as i64;
+
+    // Boundary lines x + y = sum and x - y = diff
+    let sums = sensors.iter()
+        .flat_map(|sensor| {
+            let (sum, offset) = (sensor.position.x as i64 + sensor.position.y as i64, sensor.radius() as i64 + 1);
+            vec![sum - offset, sum + offset]
+        })
+        .collect::<Vec<_>>();
+    let diffs = sensors.iter()
+        .flat_map(|sensor| {
+            let (diff, offset) = (sensor.position.x as i64 - sensor.position.y as i64, sensor.radius() as i64 + 1);
+            vec![diff - offset, diff + offset]
+        })
         .collect::<Vec<_>>();
 
-    ranges_for_y_coord.sort();
-    ranges_for_y_coord.reverse();
+    let mut candidates = vec![(0, 0), (max, 0), (0, max), (max, max)];
+
+    for &sum in sums.iter() {
+        for &diff in diffs.iter() {
+            // Lines of odd sum + diff cross between points, take the nearest points on both lines
+            let x = (sum + diff).div_euclid(2);
+            candidates.extend([(x, sum - x), (x + 1, sum - x - 1), (x, x - diff), (x + 1, x + 1 - diff)]);
+        }
+    }
 
-    let num_points = count_points_in_ranges(ranges_for_y_coord);
+    for &sum in sums.iter() {
+        candidates.extend([(0, sum), (max, sum - max), (sum, 0), (sum - max, max)]);
+    }
+    for &diff in diffs.iter() {
+        candidates.extend([(0, -diff), (max, max - diff), (diff, 0), (diff + max, max)]);
+    }
 
-    let mut beacons_for_y = sensors.iter()
-        .map(|sensor| sensor.closest_beacon)
-        .filter(|beacon| beacon.y == for_y)
+    let mut uncovered = candidates.into_iter()
+        .filter(|(x, y)| (0..=max).contains(x) && (0..=max).contains(y))
+        .map(|(x, y)| Pos{x:x as i32, y:y as i32})
+        .filter(|pos| !sensors.iter().any(|sensor| sensor.covers(pos)))
         .collect::<Vec<_>>();
 
-    beacons_for_y.sort();
-    beacons_for_y.dedup();
-
-    (num_points - beacons_for_y.len()).to_string()
+    uncovered.sort();
+    uncovered.dedup();
+    uncovered
 }
 
-fn part2(input : String, max_coord:i32) -> String {
-    let sensors = input.lines().map(|line| parse_line(line)).collect::<Vec<_>>();
+fn find_distress_beacon(sensors:&[Sensor], max_coord:i32, search:Search) -> Result<Pos, String> {
+    let uncovered = match search {
+        Search::RowScan => scan_rows(sensors, max_coord),
+        Search::Boundary => search_boundaries(sensors, max_coord),
+    };
+
+    match uncovered.as_slice() {
+        [] => Err(format!("no uncovered position within 0..={}", max_coord)),
+        [pos] => Ok(*pos),
+        [first, second, ..] => Err(format!("multiple uncovered positions within 0..={}, e.g. ({},{}) and ({},{})",
+            max_coord, first.x, first.y, second.x, second.y)),
+    }
+}
 
-    let (x,y) = (0..=max_coord).into_iter().map(|y| {
-        let mut ranges_for_y_coord = sensors.iter()
-            .map(|sensor| sensor.get_range_for_y(y))
-            .filter(|range| range.is_some())
-            .map(|range| range.unwrap())
-            .collect::<Vec<_>>();
+fn part1(input : String, for_y:i32) -> String {
+    let coverage = Coverage::new(&input);
+    let beacons_for_y = coverage.beacons().iter().filter(|beacon| beacon.y == for_y).count();
 
-        ranges_for_y_coord.sort();
-        ranges_for_y_coord.reverse();
+    (coverage.covered_in_rows(for_y..=for_y) - beacons_for_y).to_string()
+}
 
-        find_non_overlap(y, 0, max_coord, ranges_for_y_coord)
-    }).find(|coord| coord.is_some())
-        .map(|res| res.unwrap())
-        .unwrap();
+fn part2(input : String, max_coord:i32, search:Search) -> Result<String, String> {
+    let sensors = input.lines().map(parse_line).collect::<Vec<_>>();
 
-    (x as usize * 4000000 + y as usize).to_string()
+    find_distress_beacon(&sensors, max_coord, search)
+        .map(|pos| (pos.x as usize * 4000000 + pos.y as usize).to_string())
 }
 
 #[cfg(test)]
@@ -165,6 +322,7 @@ mod tests {
     use std::assert_eq;
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use util::Part::{Part1, Part2};
 
     const TEST_INPUT: &str = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
 Sensor at x=9, y=16: closest beacon is at x=10, y=16
@@ -183,25 +341,79 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3";
 
     #[test]
     fn test1() {
-        assert_eq!("26", part1(TEST_INPUT.to_string(), 10));
+        assert_eq!(Ok("26".to_string()), solve_with_params(TEST_INPUT.to_string(), Part1, &Params::parse(&params(Part1), &["row=10"]).unwrap()));
+        assert_eq!(Ok("0".to_string()), solve_with_params(TEST_INPUT.to_string(), Part1, &Params::parse(&params(Part1), &["row=2147483647"]).unwrap()));
     }
 
     #[test]
     fn test_part1() {
         let input = include_str!("../../input/input_15.txt");
 
-        assert_eq!("4827924", part1(input.to_string(), 2000000));
+        assert_eq!("4827924", solve(input.to_string(), Part1));
     }
 
     #[test]
     fn test2() {
-        assert_eq!("56000011", part2(TEST_INPUT.to_string(), 20));
+        assert_eq!(Ok("56000011".to_string()), solve_with_params(TEST_INPUT.to_string(), Part2, &Params::parse(&params(Part2), &["max_coord=20"]).unwrap()));
     }
 
     #[test]
     fn test_part2() {
         let input = include_str!("../../input/input_15.txt");
 
-        assert_eq!("12977110973564", part2(input.to_string(), 4000000));
+        assert_eq!("12977110973564", solve(input.to_string(), Part2));
+    }
+
+    #[test]
+    fn test_search() {
+        let sensors = TEST_INPUT.lines().map(parse_line).collect::<Vec<_>>();
+
+        assert_eq!(Ok(Pos{x:14, y:11}), find_distress_beacon(&sensors, 20, Search::RowScan));
+        assert_eq!(Ok(Pos{x:14, y:11}), find_distress_beacon(&sensors, 20, Search::Boundary));
+
+        let input = include_str!("../../input/input_15.txt");
+        let sensors = input.lines().map(parse_line).collect::<Vec<_>>();
+
+        assert_eq!(find_distress_beacon(&sensors, 4000000, Search::RowScan), find_distress_beacon(&sensors, 4000000, Search::Boundary));
+        assert_eq!(Ok("12977110973564".to_string()), solve_with_params(input.to_string(), Part2, &Params::parse(&params(Part2), &["search=scan"]).unwrap()));
+    }
+
+    #[test]
+    fn test_search_errors() {
+        let sensors = TEST_INPUT.lines().map(parse_line).collect::<Vec<_>>();
+
+        for search in [Search::RowScan, Search::Boundary].iter() {
+            assert_eq!(Err("no uncovered position within 0..=10".to_string()), find_distress_beacon(&sensors, 10, *search));
+        }
+        assert_eq!(Err("multiple uncovered positions within 0..=30, e.g. (0,24) and (0,25)".to_string()), find_distress_beacon(&sensors, 30, Search::Boundary));
+        assert!(find_distress_beacon(&sensors, 30, Search::RowScan).is_err());
+        assert_eq!(Err("multiple uncovered positions within 0..=2147483647, e.g. (0,24) and (0,25)".to_string()),
+                   find_distress_beacon(&sensors, i32::MAX, Search::Boundary));
+        assert_eq!(Err("no uncovered position within 0..=10".to_string()),
+                   solve_with_params(TEST_INPUT.to_string(), Part2, &Params::parse(&params(Part2), &["max_coord=10"]).unwrap()));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let coverage = Coverage::new(TEST_INPUT);
+
+        assert_eq!(None, coverage.covered_by(&Pos{x:14, y:11}).map(|sensor| sensor.position));
+        assert_eq!(Some(Pos{x:8, y:7}), coverage.covered_by(&Pos{x:8, y:-2}).map(|sensor| sensor.position));
+        assert_eq!(Some(Pos{x:8, y:7}), coverage.covered_by(&Pos{x:2, y:10}).map(|sensor| sensor.position));
+        assert_eq!(27, coverage.covered_in_rows(10..=10));
+        assert_eq!(0, coverage.covered_in_rows(i32::MAX..=i32::MAX));
+        assert_eq!((0..=20).map(|y| coverage.covered_in_rows(y..=y)).sum::<usize>(), coverage.covered_in_rows(0..=20));
+        assert_eq!("P2
+8 8
+3
+0 0 1 0 0 0 0 0
+0 1 1 1 0 0 0 0
+1 1 3 1 3 3 1 0
+0 1 1 3 3 1 1 0
+0 3 2 1 3 3 1 0
+0 2 3 3 3 1 2 1
+0 1 1 3 1 3 1 0
+0 0 1 0 0 1 0 0
+", coverage.to_pgm(5));
     }
 }
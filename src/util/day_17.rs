@@ -1,14 +1,25 @@
 use std::collections::VecDeque;
-use super::Part;
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
+pub fn params(part:Part) -> Vec<Param> {
     match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
+        Part::Part1 => vec![Param::ranged("rocks", "Number of rocks to drop", 2022, 0..=i64::MAX)],
+        Part::Part2 => vec![Param::ranged("rocks", "Number of rocks to drop", 1000000000000, 0..=i64::MAX)],
     }
 }
 
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
+    Ok(match part {
+        Part::Part1 => part1(input, params.int("rocks") as usize),
+        Part::Part2 => part2(input, params.int("rocks") as usize)
+    })
+}
+
 struct Map {
     map:VecDeque<Vec<char>>,
     jet_pattern:Vec<char>,
@@ -208,15 +219,15 @@ fn detect_cycle(input:&Vec<usize>) -> usize {
     return 0;
 }
 
-fn part1(input : String) -> String {
+fn part1(input : String, rocks:usize) -> String {
     let mut map = Map::new(input);
-    let height = map.run(2022);
+    let height = map.run(rocks);
     height.to_string()
 }
 
 
 
-fn part2(input : String) -> String {
+fn part2(input : String, rocks:usize) -> String {
     let input_len = input.len();
     let mut map = Map::new(input);
     let mut deltas = vec![];
@@ -227,6 +238,11 @@ fn part2(input : String) -> String {
 
     // Simulate enough to detect cycles
     while cycle_size == 0 || min_iterations > 0 {
+        // Fewer rocks than needed to find the cycle, simply drop the rest
+        if num_rocks + input_len > rocks {
+            return map.run(rocks - num_rocks).to_string();
+        }
+
         let height = map.run(input_len);
         deltas.push(height - last_height);
         last_height = height;
@@ -239,7 +255,7 @@ fn part2(input : String) -> String {
     let height_per_cycle = (deltas.len() - cycle_size - 1..deltas.len() - 1)
         .map(|index| *deltas.get(index).unwrap()).sum::<usize>();
     let rocks_per_cycle = cycle_size * input_len;
-    let rocks_to_drop = 1000000000000 - num_rocks;
+    let rocks_to_drop = rocks - num_rocks;
     let num_remaining_cycles = rocks_to_drop / rocks_per_cycle;
     let rest_rocks = rocks_to_drop % rocks_per_cycle;
 
@@ -275,6 +291,16 @@ mod tests {
         assert_eq!("1514285714288", solve(TEST_INPUT.to_string(), Part2));
     }
 
+    #[test]
+    fn test_params() {
+        // Below the rocks simulated while looking for the cycle
+        let few_rocks = Params::parse(&params(Part2), &["rocks=2022"]).unwrap();
+        assert_eq!(Ok("3068".to_string()), solve_with_params(TEST_INPUT.to_string(), Part2, &few_rocks));
+        assert_eq!(Ok("1".to_string()), solve_with_params(TEST_INPUT.to_string(), Part2, &Params::parse(&params(Part2), &["rocks=1"]).unwrap()));
+
+        assert_eq!(Err("parameter 'rocks' must be at least 0, got '-1'".to_string()), Params::parse(&params(Part2), &["rocks=-1"]).map(|_| ()));
+    }
+
     #[test]
     fn test_part2() {
         let _input = include_str!("../../input/input_17.txt");
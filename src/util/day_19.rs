@@ -1,15 +1,29 @@
 use std::cmp::max;
-use super::Part;
+use super::{Part, Param, Params};
 use regex::Regex;
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
+pub fn params(part:Part) -> Vec<Param> {
     match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
+        Part::Part1 => vec![Param::ranged("minutes", "Minutes available to collect geodes", 24, 1..=u32::MAX as i64)],
+        Part::Part2 => vec![
+            Param::ranged("minutes", "Minutes available to collect geodes", 32, 1..=u32::MAX as i64),
+            Param::ranged("blueprints", "Number of blueprints left uneaten", 3, 1..=i64::MAX),
+        ],
     }
 }
 
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
+    Ok(match part {
+        Part::Part1 => part1(input, params.int("minutes") as u32),
+        Part::Part2 => part2(input, params.int("minutes") as u32, params.int("blueprints") as usize)
+    })
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum MineralType {
     ORE,
@@ -201,21 +215,20 @@ impl Blueprint {
     }
 }
 
-fn part1(input : String) -> String {
+fn part1(input : String, minutes:u32) -> String {
     let blueprints = input.lines().map(|line| Blueprint::parse(line)).collect::<Vec<_>>();
 
     let result = blueprints.iter()
-        .map(|blueprint|(blueprint.number, State::new().run(blueprint, 24))).collect::<Vec<_>>();
+        .map(|blueprint|(blueprint.number, State::new().run(blueprint, minutes))).collect::<Vec<_>>();
 
     result.iter().map(|(res, no)| res * no).sum::<u32>().to_string()
 }
 
-fn part2(input : String) -> String {
+fn part2(input : String, minutes:u32, num_blueprints:usize) -> String {
     let blueprints = input.lines().map(|line| Blueprint::parse(line)).collect::<Vec<_>>();
-    let first_blueprints = blueprints.chunks(3).next().unwrap();
 
-    let result = first_blueprints.iter()
-        .map(|blueprint|(blueprint.number, State::new().run(blueprint, 32))).collect::<Vec<_>>();
+    let result = blueprints.iter().take(num_blueprints)
+        .map(|blueprint|(blueprint.number, State::new().run(blueprint, minutes))).collect::<Vec<_>>();
 
     result.iter().map(|(_, res)| *res ).reduce(|a,b| a * b).unwrap().to_string()
 }
@@ -244,6 +257,10 @@ Blueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsid
     #[test]
     fn test2() {
         assert_eq!("3348", solve(TEST_INPUT.to_string(), Part2));
+        assert_eq!(Err("parameter 'blueprints' must be at least 1, got '0'".to_string()),
+                   Params::parse(&params(Part2), &["blueprints=0"]).map(|_| ()));
+        assert_eq!(Err("parameter 'minutes' must be between 1 and 4294967295, got '0'".to_string()),
+                   Params::parse(&params(Part1), &["minutes=0"]).map(|_| ()));
     }
 
     #[test]
@@ -1,88 +1,228 @@
-use std::collections::VecDeque;
-use super::Part;
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
-    match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
-    }
+pub fn params(part:Part) -> Vec<Param> {
+    let (key, rounds) = match part {
+        Part::Part1 => (1, 1),
+        Part::Part2 => (811589153, 10),
+    };
+
+    vec![
+        Param::int("key", "Decryption key every number is multiplied by", key),
+        Param::ranged("rounds", "Number of times the list is mixed", rounds, 0..=i64::MAX),
+        Param::ints("offsets", "Positions after 0 summed for the grove coordinates", &[1000, 2000, 3000]),
+    ]
 }
 
-fn parse(input:String, key:i64) -> VecDeque<(usize,i64)>{
-    input.lines()
-        .map(|line| key * line.parse::<i64>().unwrap())
-        .enumerate().collect()
+pub fn solve_with_params(input : String, _part: Part, params:&Params) -> Result<String, String> {
+    decrypt(input, params.int("key"), params.int("rounds") as usize, params.ints("offsets"))
+        .map(|grove_coordinates| grove_coordinates.to_string())
 }
 
-fn forward(mut n:i64, numbers:&mut VecDeque<(usize,i64)>) {
-    n = n % numbers.len() as i64;
-    numbers.rotate_left(n as usize);
+// Sequence numbers in blocks of about sqrt(n), moving a number only touches the blocks it leaves
+// and enters so both finding and moving it are O(sqrt(n))
+struct BlockList {
+    blocks:Vec<Vec<usize>>,
+    // Block holding each sequence number
+    block_of:Vec<usize>,
+    block_size:usize,
 }
 
-fn reverse(mut n:i64, numbers:&mut VecDeque<(usize,i64)>) {
-    n = n % numbers.len() as i64;
-    numbers.rotate_right(n as usize);
+struct Mixer {
+    numbers:Vec<i64>,
+    order:BlockList,
 }
 
-fn mix(times:usize, numbers:&mut VecDeque<(usize, i64)>) -> i64 {
+impl BlockList {
+    fn new(len:usize) -> BlockList {
+        let block_size = ((len as f64).sqrt() as usize).max(1);
+        let mut list = BlockList{blocks:vec![], block_of:vec![0; len], block_size};
+        list.rebuild((0..len).collect());
+        list
+    }
 
-    for _ in 0..times {
-        for n in 0..numbers.len() {
-            mix_seq_no(n, numbers);
+    fn rebuild(&mut self, order:Vec<usize>) {
+        self.blocks = order.chunks(self.block_size).map(|block| block.to_vec()).collect();
+        if self.blocks.is_empty() {
+            self.blocks.push(vec![]);
         }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            for &seq_no in block {
+                self.block_of[seq_no] = index;
+            }
+        }
+    }
+
+    fn to_vec(&self) -> Vec<usize> {
+        self.blocks.iter().flatten().copied().collect()
     }
 
-    let index_of_zero = get_index(0, &numbers);
-    let index_1000th = (index_of_zero + 1000) % numbers.len();
-    let index_2000th = (index_of_zero + 2000) % numbers.len();
-    let index_3000th = (index_of_zero + 3000) % numbers.len();
+    fn position(&self, seq_no:usize) -> usize {
+        let block = self.block_of[seq_no];
+        self.blocks[..block].iter().map(|block| block.len()).sum::<usize>()
+            + self.blocks[block].iter().position(|&other| other == seq_no).unwrap()
+    }
+
+    fn get(&self, mut position:usize) -> usize {
+        for block in self.blocks.iter() {
+            if position < block.len() {
+                return block[position];
+            }
+            position -= block.len();
+        }
+        panic!("position out of range");
+    }
+
+    // Returns the position the number was removed from
+    fn remove(&mut self, seq_no:usize) -> usize {
+        let position = self.position(seq_no);
+        let block = &mut self.blocks[self.block_of[seq_no]];
+        block.retain(|&other| other != seq_no);
+        position
+    }
+
+    fn insert(&mut self, mut position:usize, seq_no:usize) {
+        let mut index = 0;
+        while index + 1 < self.blocks.len() && position > self.blocks[index].len() {
+            position -= self.blocks[index].len();
+            index += 1;
+        }
+
+        self.blocks[index].insert(position, seq_no);
+        self.block_of[seq_no] = index;
 
-    [index_1000th, index_2000th, index_3000th].iter()
-        .map(|index| numbers.get(*index).unwrap())
-        .map(|(_,number)| *number)
-        .sum()
+        // Keep lookups within a block cheap
+        if self.blocks[index].len() > 2 * self.block_size {
+            let order = self.to_vec();
+            self.rebuild(order);
+        }
+    }
 }
 
-fn mix_seq_no(step_no:usize, numbers:&mut VecDeque<(usize, i64)>) {
-    // Find right sequence
-    while step_no != numbers.front().unwrap().0 {
-        forward(1, numbers);
+impl Mixer {
+    fn new(numbers:Vec<i64>) -> Mixer {
+        let order = BlockList::new(numbers.len());
+        Mixer{numbers, order}
     }
 
+    fn mix(&mut self) {
+        // The list is circular, positions only matter relative to each other
+        let others = self.numbers.len() as i64 - 1;
+        if others < 1 {
+            return;
+        }
 
-    let (seq_no, number) = numbers.pop_front().unwrap();
+        for seq_no in 0..self.numbers.len() {
+            let position = self.order.remove(seq_no) as i64;
+            // Reduced first, adding a large number could overflow
+            self.order.insert((position + self.numbers[seq_no].rem_euclid(others)).rem_euclid(others) as usize, seq_no);
+        }
+    }
 
-    if number < 0 {
-        reverse(number.abs(), numbers);
-    } else {
-        forward(number.abs(), numbers);
+    #[allow(dead_code)]
+    fn arrangement(&self) -> Vec<i64> {
+        self.order.to_vec().iter().map(|&seq_no| self.numbers[seq_no]).collect()
     }
 
-    numbers.push_front((seq_no, number));
-}
+    fn grove_coordinates(&self, offsets:&[i64]) -> Result<i64, String> {
+        let zero = self.numbers.iter().position(|&number| number == 0).ok_or("no 0 to count the offsets from")?;
+        let start = self.order.position(zero) as i64;
+        let len = self.numbers.len() as i64;
 
-fn get_index(number:i64, numbers:&VecDeque<(usize,i64)>) -> usize {
-    numbers.iter().enumerate().find(|(_,(_, num))|  *num == number).unwrap().0
+        offsets.iter()
+            .map(|offset| self.order.get((start + offset.rem_euclid(len)).rem_euclid(len) as usize))
+            .map(|seq_no| self.numbers[seq_no])
+            .try_fold(0_i64, |sum, number| sum.checked_add(number))
+            .ok_or("grove coordinates overflow".to_string())
+    }
 }
 
-fn part1(input : String) -> String {
-    let mut numbers = parse(input, 1);
-    mix(1, &mut numbers).to_string()
-}
+fn decrypt(input:String, key:i64, rounds:usize, offsets:&[i64]) -> Result<i64, String> {
+    let numbers = input.lines()
+        .map(|line| line.parse::<i64>().unwrap())
+        .map(|number| number.checked_mul(key).ok_or(format!("decrypting {} with key {} overflows", number, key)))
+        .collect::<Result<_,_>>()?;
+    let mut mixer = Mixer::new(numbers);
 
-fn part2(input : String) -> String {
-    let mut numbers = parse(input, 811589153);
-    mix(10, &mut numbers).to_string()
+    for _ in 0..rounds {
+        mixer.mix();
+    }
+
+    mixer.grove_coordinates(offsets)
 }
 
+
 #[cfg(test)]
 mod tests {
     use std::assert_eq;
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use std::collections::VecDeque;
     use util::Part::{Part1, Part2};
 
+    // The original rotating mixer, O(n^2) per round
+    fn parse(input:String, key:i64) -> VecDeque<(usize,i64)>{
+        input.lines()
+            .map(|line| key * line.parse::<i64>().unwrap())
+            .enumerate().collect()
+    }
+
+    fn forward(mut n:i64, numbers:&mut VecDeque<(usize,i64)>) {
+        n = n % numbers.len() as i64;
+        numbers.rotate_left(n as usize);
+    }
+
+    fn reverse(mut n:i64, numbers:&mut VecDeque<(usize,i64)>) {
+        n = n % numbers.len() as i64;
+        numbers.rotate_right(n as usize);
+    }
+
+    fn reference_mix(times:usize, numbers:&mut VecDeque<(usize, i64)>) -> i64 {
+
+        for _ in 0..times {
+            for n in 0..numbers.len() {
+                mix_seq_no(n, numbers);
+            }
+        }
+
+        let index_of_zero = get_index(0, &numbers);
+        let index_1000th = (index_of_zero + 1000) % numbers.len();
+        let index_2000th = (index_of_zero + 2000) % numbers.len();
+        let index_3000th = (index_of_zero + 3000) % numbers.len();
+
+        [index_1000th, index_2000th, index_3000th].iter()
+            .map(|index| numbers.get(*index).unwrap())
+            .map(|(_,number)| *number)
+            .sum()
+    }
+
+    fn mix_seq_no(step_no:usize, numbers:&mut VecDeque<(usize, i64)>) {
+        // Find right sequence
+        while step_no != numbers.front().unwrap().0 {
+            forward(1, numbers);
+        }
+
+
+        let (seq_no, number) = numbers.pop_front().unwrap();
+
+        if number < 0 {
+            reverse(number.abs(), numbers);
+        } else {
+            forward(number.abs(), numbers);
+        }
+
+        numbers.push_front((seq_no, number));
+    }
+
+    fn get_index(number:i64, numbers:&VecDeque<(usize,i64)>) -> usize {
+        numbers.iter().enumerate().find(|(_,(_, num))|  *num == number).unwrap().0
+    }
+
 
     #[test]
     fn test1() {
@@ -124,4 +264,63 @@ mod tests {
         let input = include_str!("../../input/input_20.txt");
         assert_eq!("4265712588168", solve(input.to_string(), Part2));
     }
+
+    // Circular arrangement starting from sequence number 0
+    fn canonical(arrangement:Vec<(usize, i64)>) -> Vec<(usize, i64)> {
+        let start = arrangement.iter().position(|(seq_no, _)| *seq_no == 0).unwrap();
+        arrangement[start..].iter().chain(arrangement[..start].iter()).copied().collect()
+    }
+
+    #[test]
+    fn test_against_reference() {
+        let mut seed:u64 = 20;
+        let mut random = |range:u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % range
+        };
+
+        for len in [2, 3, 7, 50, 333].iter() {
+            let mut numbers = (0..*len).map(|_| random(41) as i64 - 20).collect::<Vec<_>>();
+            numbers.push(0);
+
+            let mut reference = numbers.iter().copied().enumerate().collect::<VecDeque<_>>();
+            let mut mixer = Mixer::new(numbers.iter().map(|number| number * 811589153).collect());
+            let mut reference_scaled = numbers.iter().map(|number| number * 811589153).enumerate().collect::<VecDeque<_>>();
+            let mut unscaled = Mixer::new(numbers.clone());
+
+            for _ in 0..3 {
+                reference_mix(1, &mut reference);
+                reference_mix(1, &mut reference_scaled);
+                unscaled.mix();
+                mixer.mix();
+
+                let order = |mixer:&Mixer| mixer.order.to_vec().into_iter().map(|seq_no| (seq_no, mixer.numbers[seq_no])).collect::<Vec<_>>();
+                assert_eq!(canonical(reference.iter().copied().collect()), canonical(order(&unscaled)));
+                assert_eq!(canonical(reference_scaled.iter().copied().collect()), canonical(order(&mixer)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let input = include_str!("../../input/input_20.txt");
+        let expected = reference_mix(1, &mut parse(input.to_string(), 1));
+
+        assert_eq!(expected.to_string(), solve(input.to_string(), Part1));
+        assert_eq!(Ok(expected.to_string()), solve_with_params(input.to_string(), Part2, &Params::parse(&params(Part2), &["key=1", "rounds=1"]).unwrap()));
+
+        let example = "1\n2\n-3\n3\n-2\n0\n4";
+        let mut mixer = Mixer::new(example.lines().map(|line| line.parse::<i64>().unwrap()).collect());
+        mixer.mix();
+        // The puzzle's 1, 2, -3, 4, 0, 3, -2 seen from another starting point
+        assert_eq!(vec![-2, 1, 2, -3, 4, 0, 3], mixer.arrangement());
+        assert_eq!(Ok(4), mixer.grove_coordinates(&[1000]));
+        assert_eq!(Ok(0), mixer.grove_coordinates(&[0, 7, -7]));
+        assert_eq!(Ok(4), mixer.grove_coordinates(&[i64::MAX - 1]));
+        assert_eq!(Ok("7".to_string()), solve_with_params(example.to_string(), Part1, &Params::parse(&params(Part1), &["offsets=1,-1,7"]).unwrap()));
+        assert_eq!(Err("decrypting 2 with key 4611686018427387904 overflows".to_string()), decrypt(example.to_string(), 1 << 62, 1, &[1000]));
+        assert_eq!(Ok(i64::MAX), decrypt("0\n1".to_string(), i64::MAX, 10, &[1]));
+        assert_eq!(Err("grove coordinates overflow".to_string()), decrypt("0\n1".to_string(), i64::MAX, 1, &[1, 1]));
+        assert_eq!(Err("no 0 to count the offsets from".to_string()), decrypt("1\n2".to_string(), 1, 1, &[1000]));
+    }
 }
@@ -1,20 +1,29 @@
-use std::collections::VecDeque;
-use util::day_21::Side::{Left, Right};
-use super::Part;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
+pub fn params(part:Part) -> Vec<Param> {
     match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
+        Part::Part1 => vec![Param::text("root", "Monkey whose number is yelled", "root")],
+        Part::Part2 => vec![
+            Param::text("root", "Monkey comparing two numbers", "root"),
+            Param::text("unknown", "Monkey whose number is solved for", "humn"),
+        ],
     }
 }
-#[derive(Debug, Copy, Clone)]
-enum Side {
-    Left,
-    Right,
-}
 
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
+    match part {
+        Part::Part1 => part1(input, params.text("root")),
+        Part::Part2 => part2(input, params.text("root"), params.text("unknown"))
+    }
+}
 #[derive(Debug)]
 struct Monkey {
     name:String,
@@ -35,134 +44,331 @@ enum Operand {
     Div,
 }
 
-fn parse(line:&str) -> Monkey {
-    let fields = line.split([':',' '])
-        .filter(|field| field.len() > 0)
-        .collect::<Vec<_>>();
+struct MonkeyGraph {
+    // Input order, keeps output stable
+    names:Vec<String>,
+    operations:HashMap<String, Operation>,
+}
 
-    if fields.len() == 2 {
-        let number = fields[1].parse::<i64>().unwrap();
-        let operation = Operation::Number(number);
-        Monkey{name:fields[0].to_string(),operation}
-    } else {
-        let name = fields[0].to_string();
-        let left = fields[1].to_string();
-        let op = fields[2];
-        let right = fields[3].to_string();
+// Monkey tree with one monkey replaced by an unknown
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Unknown(String),
+    Binary(Operand, Box<Expr>, Box<Expr>),
+}
 
-        let operand = match op {
-            "+" => Operand::Plus,
-            "-" => Operand::Minus,
-            "*" => Operand::Mult,
-            "/" => Operand::Div,
-            &_ => {
-                panic!("..");
-            }
+// Always kept normalised, den > 0 and no common factor. Arithmetic is checked, None on overflow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Rational {
+    num:i128,
+    den:i128,
+}
+
+// a*x + b
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Linear {
+    a:Rational,
+    b:Rational,
+}
+
+fn gcd(a:u128, b:u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl Rational {
+    // None for a zero den as well
+    fn new(num:i128, den:i128) -> Option<Rational> {
+        let divisor = i128::try_from(gcd(num.unsigned_abs(), den.unsigned_abs())).ok()? * den.signum();
+        Some(Rational{num:num.checked_div(divisor)?, den:den.checked_div(divisor)?})
+    }
+
+    fn integer(value:i64) -> Rational {
+        Rational{num:value as i128, den:1}
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    fn checked_add(self, other:Rational) -> Option<Rational> {
+        Rational::new(self.num.checked_mul(other.den)?.checked_add(other.num.checked_mul(self.den)?)?, self.den.checked_mul(other.den)?)
+    }
+
+    fn checked_sub(self, other:Rational) -> Option<Rational> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    fn checked_neg(self) -> Option<Rational> {
+        Some(Rational{num:self.num.checked_neg()?, den:self.den})
+    }
+
+    fn checked_mul(self, other:Rational) -> Option<Rational> {
+        Rational::new(self.num.checked_mul(other.num)?, self.den.checked_mul(other.den)?)
+    }
+
+    fn checked_div(self, other:Rational) -> Option<Rational> {
+        Rational::new(self.num.checked_mul(other.den)?, self.den.checked_mul(other.num)?)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operand::Plus => "+",
+            Operand::Minus => "-",
+            Operand::Mult => "*",
+            Operand::Div => "/",
         };
-        let operation = Operation::Binary(operand, left, right);
-        Monkey{name, operation}
+        write!(f, "{}", symbol)
     }
 }
 
-fn contains_monkey(current:&String, search:&str, monkeys:&Vec<Monkey>) -> bool {
-    let monkey = monkeys.iter().find(|monkey| monkey.name.eq(current)).unwrap();
-    if monkey.name.as_str().eq(search) {
-        return true;
+impl fmt::Display for Expr {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(number) => write!(f, "{}", number),
+            Expr::Unknown(name) => write!(f, "{}", name),
+            Expr::Binary(operand, left, right) => write!(f, "({} {} {})", left, operand, right),
+        }
     }
+}
 
-    let operation = &monkey.operation;
+impl Linear {
+    fn constant(value:Rational) -> Linear {
+        Linear{a:Rational::integer(0), b:value}
+    }
 
-    match operation {
-        Operation::Number(_) => false,
-        Operation::Binary(_, left, right) => contains_monkey(left, search, monkeys) || contains_monkey(right, search, monkeys),
+    fn scale(&self, factor:Rational) -> Option<Linear> {
+        Some(Linear{a:self.a.checked_mul(factor)?, b:self.b.checked_mul(factor)?})
     }
 }
 
-fn find_path(current:&str, search:&str, monkeys:&Vec<Monkey>, path:Vec<(Side, Operand, i64)>) -> Vec<(Side, Operand, i64)> {
-    let monkey = monkeys.iter().find(|monkey| monkey.name.eq(current)).unwrap();
+impl MonkeyGraph {
+    fn new(input:&str) -> Result<MonkeyGraph, String> {
+        let mut names = vec![];
+        let mut operations = HashMap::new();
+
+        for monkey in input.lines().map(parse) {
+            if operations.contains_key(&monkey.name) {
+                return Err(format!("monkey {} is defined twice", monkey.name));
+            }
+            names.push(monkey.name.clone());
+            operations.insert(monkey.name, monkey.operation);
+        }
+
+        // Monkeys that are only waited for are reported once evaluated, as the unknown they are fine
+        let graph = MonkeyGraph{names, operations};
+
+        match graph.find_cycle() {
+            Some(cycle) => Err(format!("monkeys wait for each other: {}", cycle.join(" -> "))),
+            None => Ok(graph),
+        }
+    }
+
+    fn operands(&self, name:&str) -> Vec<&String> {
+        match self.operations.get(name) {
+            Some(Operation::Binary(_, left, right)) => vec![left, right],
+            _ => vec![],
+        }
+    }
+
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut done = HashSet::new();
 
-    if monkey.name.as_str().eq(search) {
-        // Found leaf node
-        return path;
+        self.names.iter()
+            .find_map(|name| self.find_cycle_from(name, &mut vec![], &mut done))
     }
 
-    match &monkey.operation {
-        Operation::Number(_) => panic!("...."),
-        Operation::Binary(operand, left, right) => {
-            let mut next_path = path.clone();
-            // Check which side contains the wanted monkey
-            if contains_monkey(left, search, monkeys) {
-                let right_value = resolve(right, monkeys);
-                next_path.push((Right, *operand, right_value));
-                find_path(left, search, monkeys, next_path)
-            } else {
-                let left_value = resolve(left, monkeys);
-                next_path.push((Left, *operand, left_value));
-                find_path(right, search, monkeys, next_path)
+    // Depth first, reaching a monkey that is still on the path closes a cycle
+    fn find_cycle_from<'a>(&'a self, name:&'a str, path:&mut Vec<&'a str>, done:&mut HashSet<&'a str>) -> Option<Vec<String>> {
+        if done.contains(name) {
+            return None;
+        }
+
+        if let Some(start) = path.iter().position(|&on_path| on_path == name) {
+            let mut cycle = path[start..].iter().map(|on_path| on_path.to_string()).collect::<Vec<_>>();
+            cycle.push(name.to_string());
+            return Some(cycle);
+        }
+
+        path.push(name);
+        for operand in self.operands(name) {
+            if let Some(cycle) = self.find_cycle_from(operand, path, done) {
+                return Some(cycle);
             }
         }
+        path.pop();
+
+        done.insert(name);
+        None
+    }
+
+    fn evaluate(&self, name:&str) -> Result<i64, String> {
+        self.evaluate_memo(name, &mut HashMap::new())
     }
-}
 
-fn resolve(monkey_name:&str, monkeys:&Vec<Monkey>) -> i64 {
-    let monkey = monkeys.iter().find(|m| m.name.as_str().eq(monkey_name)).unwrap();
+    // Values are remembered, monkeys waited for by several others are only evaluated once
+    fn evaluate_memo<'a>(&'a self, name:&'a str, values:&mut HashMap<&'a str, i64>) -> Result<i64, String> {
+        if let Some(value) = values.get(name) {
+            return Ok(*value);
+        }
 
-    match &monkey.operation {
-        Operation::Number(number) => *number,
-        Operation::Binary(operand, left, right) => {
-            let left_value = resolve(left.as_str(), monkeys);
-            let right_value = resolve(right.as_str(), monkeys);
+        let value = match self.operations.get(name).ok_or(format!("unknown monkey:{}", name))? {
+            Operation::Number(number) => *number,
+            Operation::Binary(operand, left, right) => {
+                let left_value = self.evaluate_memo(left, values)?;
+                let right_value = self.evaluate_memo(right, values)?;
 
-            match operand {
-                Operand::Plus => left_value + right_value,
-                Operand::Minus => left_value - right_value,
-                Operand::Mult => left_value * right_value,
-                Operand::Div => left_value / right_value,
+                match operand {
+                    Operand::Plus => left_value.checked_add(right_value),
+                    Operand::Minus => left_value.checked_sub(right_value),
+                    Operand::Mult => left_value.checked_mul(right_value),
+                    Operand::Div if right_value == 0 => return Err(format!("monkey {} divides by zero", name)),
+                    Operand::Div if left_value % right_value != 0 =>
+                        return Err(format!("monkey {} divides {} by {} with a remainder", name, left_value, right_value)),
+                    Operand::Div => left_value.checked_div(right_value),
+                }.ok_or(format!("monkey {} overflows: {} {} {}", name, left_value, operand, right_value))?
             }
+        };
+
+        values.insert(name, value);
+        Ok(value)
+    }
+
+    fn expression(&self, name:&str, unknown:&str) -> Result<Expr, String> {
+        if name == unknown {
+            return Ok(Expr::Unknown(name.to_string()));
+        }
+
+        match self.operations.get(name).ok_or(format!("unknown monkey:{}", name))? {
+            Operation::Number(number) => Ok(Expr::Number(*number)),
+            Operation::Binary(operand, left, right) => Ok(Expr::Binary(*operand,
+                Box::new(self.expression(left, unknown)?),
+                Box::new(self.expression(right, unknown)?))),
         }
     }
-}
 
+    // Infix form with the unknown kept as a name, e.g. "pppw = ((4 + (2 * (humn - 3))) / 4)"
+    #[allow(dead_code)]
+    fn describe(&self, name:&str, unknown:&str) -> Result<String, String> {
+        Ok(format!("{} = {}", name, self.expression(name, unknown)?))
+    }
 
-fn part1(input : String) -> String {
-    let monkeys = input.lines().map(|line| parse(line)).collect::<Vec<_>>();
-    resolve("root", &monkeys).to_string()
-}
+    // Graphviz digraph of every monkey root waits for, edges point to the operands
+    #[allow(dead_code)]
+    fn to_dot(&self, root:&str) -> Result<String, String> {
+        let mut lines = vec!["digraph monkeys {".to_string()];
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
 
-fn part2(input : String) -> String {
-    let monkeys = input.lines().map(|line| parse(line)).collect::<Vec<_>>();
-    let mut path = find_path("root", "humn", &monkeys, vec![]).iter().copied().collect::<VecDeque<_>>();
+        while let Some(name) = stack.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
 
-    let (_,_,mut humn_value) = path.pop_front().unwrap();
-    while !path.is_empty() {
-        let (side, operand, value) = path.pop_front().unwrap();
-        match operand {
-            Operand::Plus => {
-                match side {
-                    _ =>  humn_value = humn_value - value,
+            match self.operations.get(name).ok_or(format!("unknown monkey:{}", name))? {
+                Operation::Number(number) => lines.push(format!("    {} [label=\"{}\\n{}\"];", name, name, number)),
+                Operation::Binary(operand, left, right) => {
+                    lines.push(format!("    {} [label=\"{}\\n{}\"];", name, name, operand));
+                    lines.push(format!("    {} -> {};", name, left));
+                    lines.push(format!("    {} -> {};", name, right));
+                    stack.push(right);
+                    stack.push(left);
                 }
-            },
-            Operand::Minus => {
-                match side {
-                    Right =>  humn_value = humn_value + value,
-                    Left => humn_value =  value - humn_value,
-                }
-            },
-            Operand::Mult => {
-                match side {
-                    _ =>  humn_value = humn_value / value,
-                }
-            },
-            Operand::Div => {
-                match side {
-                    Right => humn_value *= value,
-                    Left => humn_value = value / humn_value,
-                }
-            },
+            }
+        }
+
+        lines.push("}".to_string());
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Expr {
+    // Simplifies to a*x + b, failing if the unknown ends up multiplied or divided by itself
+    fn linear(&self) -> Result<Linear, String> {
+        match self {
+            Expr::Number(number) => Ok(Linear::constant(Rational::integer(*number))),
+            Expr::Unknown(_) => Ok(Linear{a:Rational::integer(1), b:Rational::integer(0)}),
+            Expr::Binary(operand, left, right) => {
+                let (left, right) = (left.linear()?, right.linear()?);
+
+                let linear = match operand {
+                    Operand::Plus => left.a.checked_add(right.a).zip(left.b.checked_add(right.b)).map(|(a, b)| Linear{a, b}),
+                    Operand::Minus => left.a.checked_sub(right.a).zip(left.b.checked_sub(right.b)).map(|(a, b)| Linear{a, b}),
+                    Operand::Mult if left.a.is_zero() => right.scale(left.b),
+                    Operand::Mult if right.a.is_zero() => left.scale(right.b),
+                    Operand::Div if right.a.is_zero() && right.b.is_zero() => return Err("division by zero".to_string()),
+                    Operand::Div if right.a.is_zero() => Rational::integer(1).checked_div(right.b).and_then(|factor| left.scale(factor)),
+                    _ => return Err("equation is not linear".to_string()),
+                };
+                linear.ok_or("equation overflows".to_string())
+            }
         }
     }
+}
+
+// Value of unknown making both sides of the equality monkey equal
+fn solve_for(graph:&MonkeyGraph, equality:&str, unknown:&str) -> Result<i64, String> {
+    let (left, right) = match graph.expression(equality, unknown)? {
+        Expr::Binary(_, left, right) => (left.linear()?, right.linear()?),
+        _ => return Err(format!("{} does not compare two values", equality)),
+    };
+
+    // a*x + b = 0
+    let overflow = || "equation overflows".to_string();
+    let a = left.a.checked_sub(right.a).ok_or_else(overflow)?;
+    let b = left.b.checked_sub(right.b).ok_or_else(overflow)?;
+
+    if a.is_zero() {
+        return Err(if b.is_zero() {
+            format!("every value of {} is a solution", unknown)
+        } else {
+            format!("no value of {} is a solution", unknown)
+        });
+    }
+
+    match b.checked_neg().and_then(|b| b.checked_div(a)).ok_or_else(overflow)? {
+        Rational{num, den:1} => i64::try_from(num).map_err(|_| format!("{} = {} does not fit in 64 bits", unknown, num)),
+        Rational{num, den} => Err(format!("no integer solution, {} = {}/{}", unknown, num, den)),
+    }
+}
+
+fn parse(line:&str) -> Monkey {
+    let fields = line.split([':',' '])
+        .filter(|field| field.len() > 0)
+        .collect::<Vec<_>>();
+
+    if fields.len() == 2 {
+        let number = fields[1].parse::<i64>().unwrap();
+        let operation = Operation::Number(number);
+        Monkey{name:fields[0].to_string(),operation}
+    } else {
+        let name = fields[0].to_string();
+        let left = fields[1].to_string();
+        let op = fields[2];
+        let right = fields[3].to_string();
+
+        let operand = match op {
+            "+" => Operand::Plus,
+            "-" => Operand::Minus,
+            "*" => Operand::Mult,
+            "/" => Operand::Div,
+            &_ => {
+                panic!("..");
+            }
+        };
+        let operation = Operation::Binary(operand, left, right);
+        Monkey{name, operation}
+    }
+}
+
+fn part1(input : String, root:&str) -> Result<String, String> {
+    MonkeyGraph::new(&input)?.evaluate(root).map(|number| number.to_string())
+}
 
-    humn_value.to_string()
+fn part2(input : String, root:&str, unknown:&str) -> Result<String, String> {
+    solve_for(&MonkeyGraph::new(&input)?, root, unknown).map(|number| number.to_string())
 }
 
 
@@ -203,6 +409,10 @@ hmdt: 32";
     #[test]
     fn test2() {
         assert_eq!("301", solve(TEST_INPUT.to_string(), Part2));
+        assert_eq!(Err("equation is not linear".to_string()),
+                   solve_with_params(TEST_INPUT.replace("ptdq: humn - dvpt", "ptdq: humn * humn"), Part2, &Params::defaults(&params(Part2))));
+        assert_eq!(Ok("32".to_string()),
+                   solve_with_params(TEST_INPUT.to_string(), Part2, &Params::parse(&params(Part2), &["root=drzm", "unknown=zczc"]).unwrap()));
     }
 
     #[test]
@@ -210,4 +420,71 @@ hmdt: 32";
         let input = include_str!("../../input/input_21.txt");
         assert_eq!("3560324848168", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_solve_for() {
+        let graph = MonkeyGraph::new(TEST_INPUT).unwrap();
+
+        let pppw = graph.expression("pppw", "humn").unwrap();
+        assert_eq!(Ok(Linear{a:Rational::new(1, 2).unwrap(), b:Rational::new(-1, 2).unwrap()}), pppw.linear());
+
+        // humn stays 5, so pppw is 2 and sjmn has to be 2 as well
+        assert_eq!(Ok(596), solve_for(&graph, "root", "sllz"));
+        assert_eq!(Err("no integer solution, hmdt = 12/5".to_string()), solve_for(&graph, "root", "hmdt"));
+
+        let graph = MonkeyGraph::new(&TEST_INPUT.replace("ptdq: humn - dvpt", "ptdq: humn * humn")).unwrap();
+        assert_eq!(Err("equation is not linear".to_string()), solve_for(&graph, "root", "humn"));
+
+        let graph = MonkeyGraph::new(&TEST_INPUT.replace("ljgn: 2", "ljgn: 3")).unwrap();
+        assert_eq!(Err("no integer solution, humn = 605/3".to_string()), solve_for(&graph, "root", "humn"));
+
+        let graph = MonkeyGraph::new(&TEST_INPUT.replace("lgvd: ljgn * ptdq", "lgvd: ljgn * dvpt")).unwrap();
+        assert_eq!(Err("no value of humn is a solution".to_string()), solve_for(&graph, "root", "humn"));
+
+        // Solutions and intermediate results beyond 64 and 128 bits
+        let graph = MonkeyGraph::new("root: pppw + sjmn\npppw: humn / two\ntwo: 2\nsjmn: 9223372036854775807").unwrap();
+        assert_eq!(Err("humn = 18446744073709551614 does not fit in 64 bits".to_string()), solve_for(&graph, "root", "humn"));
+        let graph = MonkeyGraph::new("root: a + b\na: c / e\nc: d / e\nd: humn / e\ne: 1000000000000000000\nb: 1").unwrap();
+        assert_eq!(Err("equation overflows".to_string()), solve_for(&graph, "root", "humn"));
+
+        // The unknown does not need a monkey of its own
+        let graph = MonkeyGraph::new(&TEST_INPUT.replace("humn: 5\n", "")).unwrap();
+        assert_eq!(Ok(301), solve_for(&graph, "root", "humn"));
+    }
+
+    #[test]
+    fn test_graph() {
+        let graph = MonkeyGraph::new(TEST_INPUT).unwrap();
+
+        assert_eq!(Ok(2), graph.evaluate("pppw"));
+        assert_eq!(Ok("pppw = ((4 + (2 * (humn - 3))) / 4)".to_string()), graph.describe("pppw", "humn"));
+        assert_eq!(Ok("root = (((4 + (2 * (humn - 3))) / 4) + ((32 - 2) * 5))".to_string()), graph.describe("root", "humn"));
+        assert_eq!(Ok("drzm = (hmdt - 2)".to_string()), graph.describe("drzm", "hmdt"));
+        assert_eq!(Err("unknown monkey:none".to_string()), graph.evaluate("none"));
+
+        assert_eq!(Ok("digraph monkeys {
+    sjmn [label=\"sjmn\\n*\"];
+    sjmn -> drzm;
+    sjmn -> dbpl;
+    drzm [label=\"drzm\\n-\"];
+    drzm -> hmdt;
+    drzm -> zczc;
+    hmdt [label=\"hmdt\\n32\"];
+    zczc [label=\"zczc\\n2\"];
+    dbpl [label=\"dbpl\\n5\"];
+}".to_string()), graph.to_dot("sjmn"));
+
+        assert_eq!(Some("monkeys wait for each other: sjmn -> drzm -> hmdt -> sjmn".to_string()),
+                   MonkeyGraph::new(&TEST_INPUT.replace("hmdt: 32", "hmdt: sjmn + zczc")).err());
+        assert_eq!(Some("unknown monkey:ptdx".to_string()),
+                   MonkeyGraph::new(&TEST_INPUT.replace("ljgn * ptdq", "ljgn * ptdx")).unwrap().evaluate("root").err());
+        assert_eq!(Some("monkey dbpl is defined twice".to_string()),
+                   MonkeyGraph::new(&format!("{}\ndbpl: 6", TEST_INPUT)).err());
+        assert_eq!(Some("monkey hmdt divides by zero".to_string()),
+                   MonkeyGraph::new(&TEST_INPUT.replace("hmdt: 32", "hmdt: zczc / zero\nzero: 0")).unwrap().evaluate("root").err());
+        assert_eq!(Some("monkey pppw divides 10 by 4 with a remainder".to_string()),
+                   MonkeyGraph::new(&TEST_INPUT.replace("humn: 5", "humn: 6")).unwrap().evaluate("root").err());
+        assert_eq!(Some("monkey sjmn overflows: 9223372036854775807 * 5".to_string()),
+                   MonkeyGraph::new(&TEST_INPUT.replace("drzm: hmdt - zczc", "drzm: 9223372036854775807")).unwrap().evaluate("root").err());
+    }
 }
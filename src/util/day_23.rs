@@ -1,12 +1,32 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-use super::Part;
+use std::collections::{HashMap, HashSet};
+use super::{Part, Param, Params};
 
+#[allow(dead_code)]
 pub fn solve(input : String, part: Part) -> String {
+    solve_with_params(input, part, &Params::defaults(&params(part))).unwrap()
+}
 
-    match part {
-        Part::Part1 => part1(input),
-        Part::Part2 => part2(input)
-    }
+pub fn params(part:Part) -> Vec<Param> {
+    let mut params = match part {
+        Part::Part1 => vec![Param::ranged("rounds", "Number of rounds before measuring the empty ground", 10, 0..=i64::MAX)],
+        Part::Part2 => vec![],
+    };
+
+    params.extend([
+        Param::choice("rotation", "When the first proposal moves to the back", &["every-round", "never"]),
+        Param::choice("conflicts", "Who moves when elves propose the same position", &["nobody-moves", "first"]),
+        Param::choice("neighbourhood", "Positions checked for adjacent elves", &["moore", "von-neumann"]),
+    ]);
+    params
+}
+
+pub fn solve_with_params(input : String, part: Part, params:&Params) -> Result<String, String> {
+    let rules = Rules::with_params(params);
+
+    Ok(match part {
+        Part::Part1 => part1(input, params.int("rounds") as usize, rules),
+        Part::Part2 => part2(input, rules)
+    })
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
@@ -15,155 +35,443 @@ enum Direction {
     South,
     West,
     East,
+    NorthWest,
+    NorthEast,
+    SouthWest,
+    SouthEast,
 }
 
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Rotation {
+    // First proposal moves to the back after each round
+    EveryRound,
+    Never,
+}
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum ConflictResolution {
+    // Nobody moves if two or more elves propose the same position
+    NobodyMoves,
+    // The first elf in reading order gets the position
+    FirstInReadingOrder,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Neighbourhood {
+    // All eight surrounding positions
+    Moore,
+    // Only north, south, west and east
+    VonNeumann,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Proposal {
+    dir:Direction,
+    // Positions that must be free, relative to the elf
+    checks:Vec<Direction>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Rules {
+    proposals:Vec<Proposal>,
+    rotation:Rotation,
+    conflicts:ConflictResolution,
+    neighbourhood:Neighbourhood,
+}
+
+#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone, PartialOrd, Ord)]
 struct Position {
-    x:i32,
     y:i32,
+    x:i32,
 }
 
 struct Grid {
     elves:HashSet<Position>,
+    rules:Rules,
+    round:usize,
 }
 
-impl Position {
-    fn new(x:i32,y:i32) -> Position {
-        Position {x,y}
-    }
+// Dense grid for the standard rules, one bit per position. Bit x of a row is
+// bit x % 64 of word x / 64, so shifting a row left moves elves east.
+struct BitGrid {
+    rows:Vec<Vec<u64>>,
+    words:usize,
+    round:usize,
+}
 
-    fn adjacent_positions(&self) -> [Position;8] {
-        [Position::new(self.x-1, self.y-1),
-            Position::new(self.x, self.y-1),
-            Position::new(self.x+1, self.y-1),
-            Position::new(self.x-1, self.y+1),
-            Position::new(self.x, self.y+1),
-            Position::new(self.x+1, self.y+1),
-            Position::new(self.x-1, self.y),
-            Position::new(self.x+1, self.y)]
+impl Direction {
+    fn delta(&self) -> (i32,i32) {
+        match self {
+            Direction::North => (0,-1),
+            Direction::South => (0,1),
+            Direction::West => (-1,0),
+            Direction::East => (1,0),
+            Direction::NorthWest => (-1,-1),
+            Direction::NorthEast => (1,-1),
+            Direction::SouthWest => (-1,1),
+            Direction::SouthEast => (1,1),
+        }
     }
+}
 
-    fn next_move(&self, dir:Direction) -> Position {
-        match dir {
-            Direction::North => Position::new(self.x, self.y-1),
-            Direction::South => Position::new(self.x, self.y+1),
-            Direction::West => Position::new(self.x-1, self.y),
-            Direction::East => Position::new(self.x+1, self.y),
+impl Neighbourhood {
+    fn directions(&self) -> &'static [Direction] {
+        match self {
+            Neighbourhood::Moore => &[Direction::North, Direction::South, Direction::West, Direction::East,
+                Direction::NorthWest, Direction::NorthEast, Direction::SouthWest, Direction::SouthEast],
+            Neighbourhood::VonNeumann => &[Direction::North, Direction::South, Direction::West, Direction::East],
         }
     }
+}
+
+impl Proposal {
+    fn new(dir:Direction, checks:&[Direction]) -> Proposal {
+        Proposal{dir, checks:checks.to_vec()}
+    }
+}
 
-    fn check_direction(&self, dir:Direction) -> [Position; 3] {
-        match dir {
-            Direction::North => [Position::new(self.x-1, self.y-1), Position::new(self.x, self.y-1), Position::new(self.x+1, self.y-1)],
-            Direction::South => [Position::new(self.x-1, self.y+1), Position::new(self.x, self.y+1), Position::new(self.x+1, self.y+1)],
-            Direction::West =>  [Position::new(self.x-1, self.y-1), Position::new(self.x-1, self.y), Position::new(self.x-1, self.y+1)],
-            Direction::East =>  [Position::new(self.x+1, self.y-1), Position::new(self.x+1, self.y), Position::new(self.x+1, self.y+1)],
+impl Rules {
+    fn standard() -> Rules {
+        Rules{
+            proposals:vec![
+                Proposal::new(Direction::North, &[Direction::NorthWest, Direction::North, Direction::NorthEast]),
+                Proposal::new(Direction::South, &[Direction::SouthWest, Direction::South, Direction::SouthEast]),
+                Proposal::new(Direction::West, &[Direction::NorthWest, Direction::West, Direction::SouthWest]),
+                Proposal::new(Direction::East, &[Direction::NorthEast, Direction::East, Direction::SouthEast]),
+            ],
+            rotation:Rotation::EveryRound,
+            conflicts:ConflictResolution::NobodyMoves,
+            neighbourhood:Neighbourhood::Moore,
         }
     }
+
+    // Standard proposals, the rest as chosen
+    fn with_params(params:&Params) -> Rules {
+        let mut rules = Rules::standard();
+
+        rules.rotation = match params.choice("rotation") {
+            "never" => Rotation::Never,
+            _ => Rotation::EveryRound,
+        };
+        rules.conflicts = match params.choice("conflicts") {
+            "first" => ConflictResolution::FirstInReadingOrder,
+            _ => ConflictResolution::NobodyMoves,
+        };
+        rules.neighbourhood = match params.choice("neighbourhood") {
+            "von-neumann" => Neighbourhood::VonNeumann,
+            _ => Neighbourhood::Moore,
+        };
+        rules
+    }
+
+    fn proposal(&self, round:usize, index:usize) -> &Proposal {
+        let offset = match self.rotation {
+            Rotation::EveryRound => round,
+            Rotation::Never => 0,
+        };
+        &self.proposals[(offset + index) % self.proposals.len()]
+    }
+}
+
+impl Position {
+    fn new(x:i32,y:i32) -> Position {
+        Position {x,y}
+    }
+
+    fn next_move(&self, dir:Direction) -> Position {
+        let (dx, dy) = dir.delta();
+        Position::new(self.x + dx, self.y + dy)
+    }
 }
 
 impl Grid {
+    #[allow(dead_code)]
     fn new(input:String) -> Grid {
+        Self::with_rules(input, Rules::standard())
+    }
+
+    fn with_rules(input:String, rules:Rules) -> Grid {
         let elves = input.lines().enumerate()
-            .map(|(y, line)| line.chars().enumerate()
+            .flat_map(|(y, line)| line.chars().enumerate()
                 .map(move |(x,ch)| (x,y,ch)))
-            .flatten()
             .filter(|(_,_,ch)| *ch == '#')
             .map(|(x,y,_)| Position::new(x as i32, y as i32))
             .collect();
-        Grid{elves}
+        Grid{elves, rules, round:0}
     }
 
-    fn perform_move(&mut self, rounds:usize) -> usize {
-        let mut round = 0;
-
-        while round < rounds {
-            let mut proposed_moves = VecDeque::new();
-            let mut conflicting_moves = HashMap::new();
-            for elf in self.elves.iter() {
-                // Should move
-                let any_adjacent = elf.adjacent_positions().iter().any(|adjacent_pos| self.elves.contains(adjacent_pos));
-                if !any_adjacent {
-                    // No adjacent elves, skip move
-                    continue;
-                }
-
-                // proposed move
-                for move_no in 0..4 {
-                    let dir_no = (round + move_no) % 4;
-                    let dir = match dir_no {
-                        0 => Direction::North,
-                        1 => Direction::South,
-                        2 => Direction::West,
-                        3 => Direction::East,
-                        _ => panic!("..."),
-                    };
-
-                    let any_blocking_elf = elf.check_direction(dir).iter().any(|pos| self.elves.contains(pos));
-                    if !any_blocking_elf {
-                        let next_move = elf.next_move(dir);
-                        proposed_moves.push_back((elf.clone(),next_move.clone()));
-                        if let Some(cnt) = conflicting_moves.get_mut(&elf.next_move(dir)) {
-                            *cnt += 1;
-                        } else {
-                            conflicting_moves.insert(next_move, 1);
-                        }
-
-                        break;
-                    }
-                }
+    fn propose(&self, elf:&Position) -> Option<Position> {
+        let any_adjacent = self.rules.neighbourhood.directions().iter()
+            .any(|dir| self.elves.contains(&elf.next_move(*dir)));
+        if !any_adjacent {
+            // No adjacent elves, skip move
+            return None;
+        }
+
+        (0..self.rules.proposals.len())
+            .map(|index| self.rules.proposal(self.round, index))
+            .find(|proposal| proposal.checks.iter().all(|dir| !self.elves.contains(&elf.next_move(*dir))))
+            .map(|proposal| elf.next_move(proposal.dir))
+    }
+
+    // Performs one round, returns the moved elves as old position -> new position
+    fn step(&mut self) -> HashMap<Position,Position> {
+        let mut elves = self.elves.iter().copied().collect::<Vec<_>>();
+        elves.sort();
+
+        let mut proposed_moves = vec![];
+        let mut targets:HashMap<Position,usize> = HashMap::new();
+        for elf in elves {
+            if let Some(next_move) = self.propose(&elf) {
+                *targets.entry(next_move).or_insert(0) += 1;
+                proposed_moves.push((elf, next_move));
             }
+        }
 
-            if proposed_moves.is_empty() {
-                // No moves
-                break;
-            } else {
-                while !proposed_moves.is_empty() {
-                    // Perform moves
-                    let (elf, next_move) = proposed_moves.pop_front().unwrap();
-
-                    if *conflicting_moves.get(&next_move).unwrap() > 1 {
-                        // Skip
-                        continue;
-                    } else {
-                        // Perform move
-                        self.elves.remove(&elf);
-                        self.elves.insert(next_move);
-                    }
-                }
+        let mut moved = HashMap::new();
+        let mut taken = HashSet::new();
+        for (elf, next_move) in proposed_moves {
+            let allowed = match self.rules.conflicts {
+                ConflictResolution::NobodyMoves => targets[&next_move] == 1,
+                ConflictResolution::FirstInReadingOrder => taken.insert(next_move),
+            };
+
+            if allowed {
+                moved.insert(elf, next_move);
             }
+        }
+
+        for elf in moved.keys() {
+            self.elves.remove(elf);
+        }
+        self.elves.extend(moved.values());
 
-            round +=1;
+        self.round += 1;
+        moved
+    }
+
+    // Returns the number of the first round where no elf moved, or rounds + 1
+    fn perform_move(&mut self, rounds:usize) -> usize {
+        while self.round < rounds {
+            if self.step().is_empty() {
+                return self.round;
+            }
         }
 
-        round+1
+        self.round + 1
     }
 
     fn num_elves(&self) -> usize {
         self.elves.len()
     }
 
-    fn get_area(&self) -> usize {
+    fn bounds(&self) -> (Position, Position) {
         let y_max = self.elves.iter().map(|e| e.y).max().unwrap();
         let x_max = self.elves.iter().map(|e| e.x).max().unwrap();
         let y_min = self.elves.iter().map(|e| e.y).min().unwrap();
         let x_min = self.elves.iter().map(|e| e.x).min().unwrap();
-        ((x_max - x_min + 1) * (y_max - y_min + 1)) as usize
+        (Position::new(x_min, y_min), Position::new(x_max, y_max))
+    }
+
+    fn get_area(&self) -> usize {
+        let (min, max) = self.bounds();
+        ((max.x - min.x + 1) * (max.y - min.y + 1)) as usize
+    }
+
+    #[allow(dead_code)]
+    fn render(&self) -> String {
+        let (min, max) = self.bounds();
+
+        (min.y..=max.y)
+            .map(|y| (min.x..=max.x)
+                .map(|x| if self.elves.contains(&Position::new(x, y)) { '#' } else { '.' })
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+const ROW_PADDING:usize = 8;
+
+impl BitGrid {
+    fn new(input:String) -> BitGrid {
+        let width = input.lines().map(|line| line.len()).max().unwrap_or(0);
+
+        // One empty word on each side
+        let words = width / 64 + 3;
+        let mut rows = vec![vec![0_u64; words]; ROW_PADDING];
+
+        for line in input.lines() {
+            let mut row = vec![0_u64; words];
+            line.chars().enumerate()
+                .filter(|(_,ch)| *ch == '#')
+                .for_each(|(x,_)| row[x / 64 + 1] |= 1 << (x % 64));
+            rows.push(row);
+        }
+
+        rows.extend(vec![vec![0_u64; words]; ROW_PADDING]);
+        BitGrid{rows, words, round:0}
+    }
+
+    fn shift_east(row:&[u64]) -> Vec<u64> {
+        (0..row.len())
+            .map(|w| (row[w] << 1) | if w > 0 { row[w - 1] >> 63 } else { 0 })
+            .collect()
+    }
+
+    fn shift_west(row:&[u64]) -> Vec<u64> {
+        (0..row.len())
+            .map(|w| (row[w] >> 1) | if w + 1 < row.len() { row[w + 1] << 63 } else { 0 })
+            .collect()
+    }
+
+    fn ensure_margin(&mut self) {
+        // Keep elves two rows away from the top and bottom, and out of the outermost words
+        let height = self.rows.len();
+        if self.rows[..2].iter().chain(self.rows[height - 2..].iter()).flatten().any(|&word| word != 0) {
+            let empty = vec![vec![0_u64; self.words]; ROW_PADDING];
+            self.rows.splice(0..0, empty.clone());
+            self.rows.extend(empty);
+        }
+
+        if self.rows.iter().any(|row| row[0] != 0) {
+            self.rows.iter_mut().for_each(|row| row.insert(0, 0));
+            self.words += 1;
+        }
+
+        if self.rows.iter().any(|row| row[row.len() - 1] != 0) {
+            self.rows.iter_mut().for_each(|row| row.push(0));
+            self.words += 1;
+        }
+    }
+
+    fn propose_row(&self, y:usize) -> Vec<Vec<u64>> {
+        let (north, current, south) = (&self.rows[y - 1], &self.rows[y], &self.rows[y + 1]);
+        let (north_west, north_east) = (Self::shift_east(north), Self::shift_west(north));
+        let (west, east) = (Self::shift_east(current), Self::shift_west(current));
+        let (south_west, south_east) = (Self::shift_east(south), Self::shift_west(south));
+        let mut proposals = vec![vec![0_u64; self.words]; 4];
+
+        for w in 0..self.words {
+            let blocked = [
+                north_west[w] | north[w] | north_east[w],
+                south_west[w] | south[w] | south_east[w],
+                north_west[w] | west[w] | south_west[w],
+                north_east[w] | east[w] | south_east[w],
+            ];
+
+            let mut remaining = current[w] & (blocked[0] | blocked[1] | west[w] | east[w]);
+            for offset in 0..4 {
+                let dir = (self.round + offset) % 4;
+                proposals[dir][w] = remaining & !blocked[dir];
+                remaining &= !proposals[dir][w];
+            }
+        }
+
+        proposals
+    }
+
+    // Performs one round, returns the number of elves that moved
+    fn step(&mut self) -> usize {
+        self.ensure_margin();
+
+        let height = self.rows.len();
+        let empty = vec![0_u64; self.words];
+
+        // Proposals per row and direction (north, south, west, east)
+        let proposals = (0..height)
+            .map(|y| if y == 0 || y == height - 1 { vec![empty.clone(); 4] } else { self.propose_row(y) })
+            .collect::<Vec<_>>();
+
+        // Only elves moving in opposite directions can propose the same position
+        let mut rows = self.rows.clone();
+        let mut moved = 0;
+
+        for y in 1..height - 1 {
+            let north = &proposals[y][0];
+            let south = &proposals[y][1];
+            let south_from_above = if y >= 2 { &proposals[y - 2][1] } else { &empty };
+            let north_from_below = if y + 2 < height { &proposals[y + 2][0] } else { &empty };
+            let west_blocked = Self::shift_east(&Self::shift_east(&proposals[y][3]));
+            let east_blocked = Self::shift_west(&Self::shift_west(&proposals[y][2]));
+
+            let mut moving = vec![empty.clone(); 4];
+            for w in 0..self.words {
+                moving[0][w] = north[w] & !south_from_above[w];
+                moving[1][w] = south[w] & !north_from_below[w];
+                moving[2][w] = proposals[y][2][w] & !west_blocked[w];
+                moving[3][w] = proposals[y][3][w] & !east_blocked[w];
+            }
+
+            let moved_west = Self::shift_west(&moving[2]);
+            let moved_east = Self::shift_east(&moving[3]);
+            for w in 0..self.words {
+                let leaving = moving.iter().fold(0, |acc, dir| acc | dir[w]);
+                moved += leaving.count_ones() as usize;
+                rows[y][w] &= !leaving;
+                rows[y][w] |= moved_west[w] | moved_east[w];
+                rows[y - 1][w] |= moving[0][w];
+                rows[y + 1][w] |= moving[1][w];
+            }
+        }
+
+        self.rows = rows;
+        self.round += 1;
+        moved
+    }
+
+    // Returns the number of the first round where no elf moved, or rounds + 1
+    fn perform_move(&mut self, rounds:usize) -> usize {
+        while self.round < rounds {
+            if self.step() == 0 {
+                return self.round;
+            }
+        }
+
+        self.round + 1
+    }
+
+    fn num_elves(&self) -> usize {
+        self.rows.iter().flatten().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn get_area(&self) -> usize {
+        let occupied = self.rows.iter()
+            .enumerate()
+            .filter(|(_, row)| row.iter().any(|&word| word != 0))
+            .map(|(y,_)| y)
+            .collect::<Vec<_>>();
+
+        let columns = self.rows.iter().fold(vec![0_u64; self.words], |acc, row| acc.iter().zip(row).map(|(a, b)| a | b).collect());
+        let x_min = columns.iter().position(|&word| word != 0).map(|w| w * 64 + columns[w].trailing_zeros() as usize).unwrap();
+        let x_max = columns.iter().rposition(|&word| word != 0).map(|w| w * 64 + 63 - columns[w].leading_zeros() as usize).unwrap();
+
+        (x_max - x_min + 1) * (occupied.last().unwrap() - occupied.first().unwrap() + 1)
     }
 }
 
-fn part1(input : String) -> String {
-    let mut grid = Grid::new(input);
-    grid.perform_move(10);
-    (grid.get_area() - grid.num_elves()).to_string()
+// The bit grid only knows the standard rules
+fn part1(input : String, rounds:usize, rules:Rules) -> String {
+    if rules == Rules::standard() {
+        let mut grid = BitGrid::new(input);
+        grid.perform_move(rounds);
+        (grid.get_area() - grid.num_elves()).to_string()
+    } else {
+        let mut grid = Grid::with_rules(input, rules);
+        grid.perform_move(rounds);
+        (grid.get_area() - grid.num_elves()).to_string()
+    }
 }
 
-fn part2(input : String) -> String {
-    let mut grid = Grid::new(input);
-    grid.perform_move(1_000_000).to_string()
+fn part2(input : String, rules:Rules) -> String {
+    if rules == Rules::standard() {
+        BitGrid::new(input).perform_move(1_000_000).to_string()
+    } else {
+        Grid::with_rules(input, rules).perform_move(1_000_000).to_string()
+    }
 }
 
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -225,4 +533,83 @@ mod tests {
 
         assert_eq!("918", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_step() {
+        let mut grid = Grid::new(TEST_INPUT1.to_string());
+
+        let moved = grid.step();
+        assert_eq!(3, moved.len());
+        assert_eq!(Some(&Position::new(2, 0)), moved.get(&Position::new(2, 1)));
+        assert_eq!("##
+..
+#.
+.#
+#.", grid.render());
+
+        grid.step();
+        grid.step();
+        assert_eq!("..#..
+....#
+#....
+....#
+.....
+..#..", grid.render());
+        assert!(grid.step().is_empty());
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut rules = Rules::standard();
+        rules.rotation = Rotation::Never;
+        rules.conflicts = ConflictResolution::FirstInReadingOrder;
+        rules.neighbourhood = Neighbourhood::VonNeumann;
+
+        let mut grid = Grid::with_rules(TEST_INPUT1.to_string(), rules);
+        let moved = grid.step();
+
+        // (2,2) and (2,4) both propose (2,3), the first in reading order gets it
+        assert_eq!(4, moved.len());
+        assert_eq!(Some(&Position::new(2, 3)), moved.get(&Position::new(2, 2)));
+        assert_eq!(None, moved.get(&Position::new(2, 4)));
+
+        // North is still proposed first, the standard rules would move south
+        let moved = grid.step();
+        assert_eq!(Some(&Position::new(2, -1)), moved.get(&Position::new(2, 0)));
+
+        // Non standard rules are solved on the HashSet grid
+        let params = Params::parse(&params(Part1), &["rounds=2", "rotation=never", "conflicts=first", "neighbourhood=von-neumann"]).unwrap();
+        assert_eq!(Ok("9".to_string()), solve_with_params(TEST_INPUT1.to_string(), Part1, &params));
+    }
+
+    #[test]
+    fn test_bit_grid() {
+        for input in [TEST_INPUT1, TEST_INPUT2] {
+            let mut grid = Grid::new(input.to_string());
+            let mut bit_grid = BitGrid::new(input.to_string());
+
+            for _ in 0..20 {
+                assert_eq!(grid.step().len(), bit_grid.step());
+                assert_eq!(grid.get_area(), bit_grid.get_area());
+                assert_eq!(grid.num_elves(), bit_grid.num_elves());
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_grids() {
+        // cargo test --release bench_grids -- --ignored --nocapture
+        let input = include_str!("../../input/input_23.txt");
+
+        let start = std::time::Instant::now();
+        let rounds = Grid::new(input.to_string()).perform_move(1_000_000);
+        println!("HashSet grid: {} rounds in {:?}", rounds, start.elapsed());
+
+        let start = std::time::Instant::now();
+        let bit_rounds = BitGrid::new(input.to_string()).perform_move(1_000_000);
+        println!("Bit grid: {} rounds in {:?}", bit_rounds, start.elapsed());
+
+        assert_eq!(rounds, bit_rounds);
+    }
 }
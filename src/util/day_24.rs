@@ -9,8 +9,61 @@ pub fn solve(input : String, part: Part) -> String {
     }
 }
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+    Wait,
+}
+
+impl Move {
+    fn delta(&self) -> (i32,i32) {
+        match self {
+            Move::Up => (0,-1),
+            Move::Down => (0,1),
+            Move::Left => (-1,0),
+            Move::Right => (1,0),
+            Move::Wait => (0,0),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Move::Up => "move up",
+            Move::Down => "move down",
+            Move::Left => "move left",
+            Move::Right => "move right",
+            Move::Wait => "wait",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Trip {
+    route:Vec<Move>,
+    // Minutes spent on each leg between two waypoints
+    legs:Vec<usize>,
+}
+
+// Search state, linked to the state it was reached from
+#[derive(Debug, Copy, Clone)]
+struct State {
+    pos:(i32,i32),
+    steps:usize,
+    leg:usize,
+    parent:usize,
+    last_move:Move,
+}
+
 struct Map {
     map:Vec<Vec<u8>>,
+    width:usize,
+    height:usize,
+    period:usize,
+    // free[time % period][y][x]
+    free:Vec<Vec<Vec<bool>>>,
 }
 
 impl Map {
@@ -22,7 +75,7 @@ impl Map {
     const WALL:u8 = 0b10000;
 
     fn parse(input:String) -> Map {
-        Map{map:input.lines().map(|row|
+        let map:Vec<Vec<u8>> = input.lines().map(|row|
             row.chars().map(|ch| {
                 match ch {
                     '.' => Map::FREE,
@@ -34,132 +87,185 @@ impl Map {
                     _ => panic!("unexpected char:{}",{ch}),
                 }
             }).collect())
-            .collect()
-        }
+            .collect();
+
+        let width = map.first().unwrap().len() - 2;
+        let height = map.len() - 2;
+        let period = width / Self::gcd(width, height) * height;
+
+        let mut result = Map{map, width, height, period, free:vec![]};
+        result.free = (0..period)
+            .map(|time| (0..result.height + 2)
+                .map(|y| (0..result.width + 2)
+                    .map(|x| result.get_pos(x as i32, y as i32, time) == Map::FREE)
+                    .collect())
+                .collect())
+            .collect();
+
+        result
     }
 
-    fn move_blizzards(&mut self) {
-        let mut next_positions = vec![];
-        let max_y = self.map.len() - 2;
-        let max_x = self.map.first().unwrap().len() - 2;
-        for y in 1..=max_y {
-            let row = self.map.get_mut(y).unwrap();
-            for x in 1..=max_x {
-                let item = row.get_mut(x).unwrap();
-                if *item != Map::FREE {
-                    let blizzards = [Map::UP, Map::DOWN, Map::LEFT, Map::RIGHT];
-                    for blizz in blizzards {
-                        if let Some((next_x, next_y)) = match blizz & *item {
-                            Map::UP => {
-                                if y == 1 {
-                                    Some((x, max_y))
-                                } else {
-                                    Some((x, y - 1))
-                                }
-                            },
-                            Map::DOWN => {
-                                if y == max_y {
-                                    Some((x, 1))
-                                } else {
-                                    Some((x, y + 1))
-                                }
-                            },
-                            Map::LEFT => {
-                                if x == 1 {
-                                    Some((max_x, y))
-                                } else {
-                                    Some((x - 1, y))
-                                }
-                            },
-                            Map::RIGHT => {
-                                if x == max_x {
-                                    Some((1, y))
-                                } else {
-                                    Some((x + 1, y))
-                                }
-                            },
-                            _ => None,
-                        } {
-                            next_positions.push((blizz & *item, next_x, next_y));
-                        }
-                    }
-                    // Clear position
-                    *item = Map::FREE;
-                }
-            }
-        }
+    fn gcd(a:usize, b:usize) -> usize {
+        if b == 0 { a } else { Self::gcd(b, a % b) }
+    }
 
-        // Add next positions
-        for (blizz, next_x, next_y) in next_positions {
-            *self.map.get_mut(next_y).unwrap().get_mut(next_x).unwrap() |= blizz;
+    // Blizzards (or wall) at a position and time, found by moving each blizzard kind back in time
+    fn get_pos(&self, x:i32, y:i32, time:usize) -> u8 {
+        let (x, y) = (x as usize, y as usize);
+        if self.map[y][x] == Map::WALL || y == 0 || y == self.height + 1 {
+            return self.map[y][x];
         }
+
+        // Interior coordinates
+        let (ix, iy) = (x - 1, y - 1);
+        let (w, h) = (self.width, self.height);
+        let (tx, ty) = (time % w, time % h);
+
+        (self.map[iy + 1][(ix + w - tx) % w + 1] & Map::RIGHT) |
+            (self.map[iy + 1][(ix + tx) % w + 1] & Map::LEFT) |
+            (self.map[(iy + h - ty) % h + 1][ix + 1] & Map::DOWN) |
+            (self.map[(iy + ty) % h + 1][ix + 1] & Map::UP)
+    }
+
+    fn is_free(&self, x:i32, y:i32, time:usize) -> bool {
+        x >= 0 && y >= 0 && (y as usize) < self.height + 2 && (x as usize) < self.width + 2 &&
+            self.free[time % self.period][y as usize][x as usize]
     }
 
     fn get_exit(&self) -> (i32,i32) {
-        (self.map.first().unwrap().len() as i32 - 2,self.map.len() as i32 - 1)
+        (self.width as i32,self.height as i32 + 1)
     }
 
     fn get_start(&self) -> (i32,i32) {
         (1,0)
     }
 
-    fn find_shortest_path(&mut self, start_pos:(i32,i32), end_pos:(i32, i32)) -> usize {
+    // Number of minutes needed to get from start to end when leaving at start_time
+    fn find_shortest_path(&self, start_pos:(i32,i32), end_pos:(i32, i32), start_time:usize) -> Option<usize> {
+        self.find_route(start_pos, end_pos, start_time).map(|route| route.len())
+    }
+
+    fn find_route(&self, start_pos:(i32,i32), end_pos:(i32, i32), start_time:usize) -> Option<Vec<Move>> {
+        self.plan_trip(&[start_pos, end_pos], start_time).map(|trip| trip.route)
+    }
+
+    // Fastest trip visiting the waypoints in order. The leg is part of the search state, as arriving at an
+    // interior waypoint as early as possible isn't always best when blizzards prevent waiting there.
+    fn plan_trip(&self, waypoints:&[(i32,i32)], start_time:usize) -> Option<Trip> {
+        let start_pos = *waypoints.first()?;
+        if !self.is_free(start_pos.0, start_pos.1, start_time) {
+            return None;
+        }
+
+        let next_leg = |pos:(i32,i32), mut leg:usize| {
+            while leg + 1 < waypoints.len() && pos == waypoints[leg + 1] {
+                leg += 1;
+            }
+            leg
+        };
+
+        let mut states = vec![State{pos:start_pos, steps:0, leg:next_leg(start_pos, 0), parent:0, last_move:Move::Wait}];
         let mut queue = VecDeque::new();
-        let mut blizzard_step = 0;
         let mut visited = HashSet::new();
-        let max_y = self.map.len() as i32 - 1;
 
-        queue.push_back((0,start_pos.0,start_pos.1));
+        queue.push_back(0);
+        visited.insert((start_time % self.period, start_pos, states[0].leg));
 
-        while !queue.is_empty() {
-            let (step_no, x,y) = queue.pop_front().unwrap();
-            if visited.contains(&(step_no, x , y)) {
-                continue;
-            } else {
-                visited.insert((step_no, x, y));
+        while let Some(index) = queue.pop_front() {
+            let State{pos:(x, y), steps, leg, ..} = states[index];
+            if leg + 1 == waypoints.len() {
+                return Some(Self::build_trip(&states, index, waypoints.len()));
             }
 
-            if (x,y).eq(&end_pos) {
-                // Found solution
-                return blizzard_step - 1
+            let time = start_time + steps + 1;
+            for next_move in [Move::Down, Move::Right, Move::Wait, Move::Up, Move::Left] {
+                let (dx, dy) = next_move.delta();
+                let next_pos = (x + dx, y + dy);
+                let leg = next_leg(next_pos, leg);
+                if self.is_free(next_pos.0, next_pos.1, time) && visited.insert((time % self.period, next_pos, leg)) {
+                    states.push(State{pos:next_pos, steps:steps + 1, leg, parent:index, last_move:next_move});
+                    queue.push_back(states.len() - 1);
+                }
             }
+        }
+
+        None
+    }
+
+    fn build_trip(states:&[State], mut index:usize, num_waypoints:usize) -> Trip {
+        let mut route = vec![];
+        let mut arrivals = vec![0; num_waypoints];
+
+        loop {
+            let state = states[index];
+            let parent_leg = if index == 0 { 0 } else { states[state.parent].leg };
+            (parent_leg..state.leg).for_each(|reached| arrivals[reached + 1] = state.steps);
 
-            // Check next moves, move blizzards
-            if step_no != blizzard_step {
-                self.move_blizzards();
-                blizzard_step = step_no;
-                visited.clear();
+            if index == 0 {
+                break;
             }
 
-            let candidate_positions = [(x,y), (x+1,y),(x-1,y),(x,y-1),(x,y+1)];
-            candidate_positions.iter()
-                .filter(|(x,y)| *x >= 0 && *y >= 0 && *y <= max_y && self.get_pos(*x,*y) == Map::FREE)
-                .for_each(|(x,y)| {
-                    queue.push_back((blizzard_step+1, *x, *y));
-                })
+            route.push(state.last_move);
+            index = state.parent;
         }
 
-        panic!("No solution");
+        route.reverse();
+        let legs = arrivals.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        Trip{route, legs}
     }
 
-    fn get_pos(&self,x:i32, y:i32) -> u8 {
-        *self.map.get(y as usize).unwrap().get(x as usize).unwrap()
+    fn render(&self, time:usize, expedition:(i32,i32)) -> String {
+        (0..self.height as i32 + 2)
+            .map(|y| (0..self.width as i32 + 2)
+                .map(|x| {
+                    let blizzards = self.get_pos(x, y, time);
+                    if (x, y) == expedition {
+                        'E'
+                    } else {
+                        match blizzards {
+                            Map::FREE => '.',
+                            Map::WALL => '#',
+                            Map::UP => '^',
+                            Map::DOWN => 'v',
+                            Map::LEFT => '<',
+                            Map::RIGHT => '>',
+                            _ => std::char::from_digit(blizzards.count_ones(), 10).unwrap(),
+                        }
+                    }
+                })
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Renders the valley for every minute of the route, in the same format as the puzzle
+    #[allow(dead_code)]
+    fn replay(&self, start_pos:(i32,i32), start_time:usize, route:&[Move]) -> String {
+        let mut pos = start_pos;
+        let mut frames = vec![format!("Initial state:\n{}", self.render(start_time, pos))];
+
+        for (minute, next_move) in route.iter().enumerate() {
+            let (dx, dy) = next_move.delta();
+            pos = (pos.0 + dx, pos.1 + dy);
+            frames.push(format!("Minute {}, {}:\n{}", start_time + minute + 1, next_move.describe(), self.render(start_time + minute + 1, pos)));
+        }
+
+        frames.join("\n\n")
     }
 }
 
 fn part1(input : String) -> String {
-    let mut map = Map::parse(input);
-    map.find_shortest_path(map.get_start(), map.get_exit()).to_string()
+    let map = Map::parse(input);
+    map.find_shortest_path(map.get_start(), map.get_exit(), 0).unwrap().to_string()
 }
 
 fn part2(input : String) -> String {
-    let mut map = Map::parse(input);
-    let first_path = map.find_shortest_path(map.get_start(), map.get_exit());
-    let second_path = map.find_shortest_path(map.get_exit(), map.get_start()) + 1;
-    let third_path = map.find_shortest_path(map.get_start(), map.get_exit()) + 1;
-    (first_path + second_path + third_path).to_string()
+    let map = Map::parse(input);
+    let waypoints = [map.get_start(), map.get_exit(), map.get_start(), map.get_exit()];
+    map.plan_trip(&waypoints, 0).unwrap().route.len().to_string()
 }
 
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -194,4 +300,83 @@ mod tests {
         let input = include_str!("../../input/input_24.txt");
         assert_eq!("842", solve(input.to_string(), Part2));
     }
+
+    #[test]
+    fn test_period() {
+        let map = Map::parse(TEST_INPUT1.to_string());
+
+        assert_eq!(12, map.period);
+        assert_eq!(Map::RIGHT, map.get_pos(1, 1, 0));
+        assert_eq!(Map::FREE, map.get_pos(1, 1, 1));
+        assert_eq!(Map::RIGHT | Map::LEFT | Map::DOWN, map.get_pos(3, 1, 1));
+        for time in 0..map.period {
+            for (x, y) in [(1, 1), (3, 2), (6, 4)] {
+                assert_eq!(map.get_pos(x, y, time), map.get_pos(x, y, time + map.period));
+            }
+        }
+
+        // Searches don't change the map, so they can be repeated in any order
+        assert_eq!(Some(23), map.find_shortest_path(map.get_exit(), map.get_start(), 18));
+        assert_eq!(Some(18), map.find_shortest_path(map.get_start(), map.get_exit(), 0));
+        assert_eq!(Some(13), map.find_shortest_path(map.get_start(), map.get_exit(), 41));
+    }
+
+    #[test]
+    fn test_route() {
+        let map = Map::parse(TEST_INPUT1.to_string());
+        let route = map.find_route(map.get_start(), map.get_exit(), 0).unwrap();
+
+        // Same route as in the puzzle description
+        assert_eq!(vec![Move::Down, Move::Down, Move::Wait, Move::Up, Move::Right, Move::Right, Move::Down, Move::Left,
+                        Move::Up, Move::Right, Move::Wait, Move::Down, Move::Down, Move::Right, Move::Right, Move::Right,
+                        Move::Down, Move::Down], route);
+
+        let replay = map.replay(map.get_start(), 0, &route);
+        let frames = replay.split("\n\n").collect::<Vec<_>>();
+
+        assert_eq!(19, frames.len());
+        assert_eq!("Initial state:
+#E######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#", frames[0]);
+        assert_eq!("Minute 1, move down:
+#.######
+#E>3.<.#
+#<..<<.#
+#>2.22.#
+#>v..^<#
+######.#", frames[1]);
+        assert!(frames[18].ends_with("######E#"));
+    }
+    #[test]
+    fn test_trip() {
+        let map = Map::parse(TEST_INPUT1.to_string());
+
+        let trip = map.plan_trip(&[map.get_start(), map.get_exit(), map.get_start(), map.get_exit()], 0).unwrap();
+        assert_eq!(vec![18, 23, 13], trip.legs);
+        assert_eq!(54, trip.route.len());
+
+        let waypoints = [(3, 2), (6, 4), map.get_start()];
+        let trip = map.plan_trip(&[map.get_start(), waypoints[0], waypoints[1], waypoints[2]], 0).unwrap();
+        assert_eq!(trip.route.len(), trip.legs.iter().sum::<usize>());
+        assert_eq!(3, trip.legs.len());
+
+        // Replaying the route passes the waypoints in order
+        let mut pos = map.get_start();
+        let mut next_waypoint = 0;
+        for next_move in trip.route.iter() {
+            let (dx, dy) = next_move.delta();
+            pos = (pos.0 + dx, pos.1 + dy);
+            if waypoints.get(next_waypoint) == Some(&pos) {
+                next_waypoint += 1;
+            }
+        }
+        assert_eq!(3, next_waypoint);
+
+        assert_eq!(Some(Trip{route:vec![], legs:vec![0]}), map.plan_trip(&[map.get_start(), map.get_start()], 0));
+        assert_eq!(None, map.plan_trip(&[(1, 1), map.get_exit()], 0));
+    }
 }
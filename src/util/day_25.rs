@@ -1,4 +1,5 @@
 use super::Part;
+use super::numeral::{Decimal, Numeral, Snafu};
 
 pub fn solve(input : String, part: Part) -> String {
 
@@ -8,75 +9,64 @@ pub fn solve(input : String, part: Part) -> String {
     }
 }
 
-fn snafu_to_decimal(input:&str) -> i64 {
-    input.chars().enumerate().map(|(i, ch)| {
-        let exp = input.len() - i - 1;
-        let snafu_value = match ch {
-            '2' => 2,
-            '1' => 1,
-            '0' => 0,
-            '-' => -1,
-            '=' => -2,
-            _ => panic!("unexpected char"),
-        };
-        snafu_value * i64::pow(5, exp as u32)
-    }).sum()
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Conversion {
+    Encode,
+    Decode,
 }
 
-fn max_value(digits:usize) -> i64 {
-    (0..digits).into_iter().map(|n| 2 * i64::pow(5, n as u32)).sum()
-}
-
-fn get_number_of_snafu_digits(decimal_value:i64) -> usize {
-    let mut num_digits = 1;
-    loop {
-        if max_value(num_digits) > decimal_value {
-            return num_digits
-        } else {
-            num_digits += 1;
+impl Conversion {
+    pub fn parse(name:&str) -> Option<Conversion> {
+        match name {
+            "encode" => Some(Conversion::Encode),
+            "decode" => Some(Conversion::Decode),
+            _ => None,
         }
     }
-}
 
-fn decimal_to_snafu(decimal_value:i64) -> String {
-    decimal_to_snafu_rec(get_number_of_snafu_digits(decimal_value), decimal_value, vec![])
-}
+    fn headers(&self) -> (&'static str, &'static str) {
+        match self {
+            Conversion::Encode => ("decimal", "snafu"),
+            Conversion::Decode => ("snafu", "decimal"),
+        }
+    }
 
-fn decimal_to_snafu_rec(snafu_digit_no:usize, decimal_value:i64, mut result:Vec<char>) -> String {
-    if snafu_digit_no == 0 {
-        return result.iter().collect();
-    };
-
-    let mut min_diff = u64::MAX;
-    let mut next_snafu_value = 0;
-    let mut rest = 0;
-    for a in [2,1,0,-1,-2] {
-        let snafu_value = a * i64::pow(5, snafu_digit_no as u32 - 1);
-        if decimal_value.abs_diff(snafu_value) < min_diff {
-            min_diff = decimal_value.abs_diff(snafu_value);
-            next_snafu_value = a;
-            rest = decimal_value - snafu_value;
+    pub fn convert(&self, value:&str) -> Result<String, String> {
+        match self {
+            Conversion::Encode => value.parse::<Numeral<Decimal>>().map(|decimal| decimal.convert::<Snafu>().to_string()),
+            Conversion::Decode => value.parse::<Numeral<Snafu>>().map(|snafu| snafu.convert::<Decimal>().to_string()),
         }
     }
+}
 
-    result.push(match next_snafu_value {
-        2 => '2',
-        1 => '1',
-        0 => '0',
-        -1 => '-',
-        -2 => '=',
-        _ => panic!("..."),
-    });
+// Right aligned table of every value and its conversion, together with the number of values that
+// failed. Errors are left out of the column width so they don't push the results apart.
+pub fn conversion_table(conversion:Conversion, values:&[&str]) -> (String, usize) {
+    let (from, to) = conversion.headers();
+    let results = values.iter().map(|value| conversion.convert(value)).collect::<Vec<_>>();
 
-    return decimal_to_snafu_rec(snafu_digit_no - 1, rest, result);
-}
+    let left = values.iter().map(|value| value.len()).chain(std::iter::once(from.len())).max().unwrap();
+    let right = results.iter().flatten().map(|result| result.len()).chain(std::iter::once(to.len())).max().unwrap();
+
+    let rows = values.iter().zip(results.iter())
+        .map(|(value, result)| match result {
+            Ok(result) => format!("{:>left$}  {:>right$}", value, result, left = left, right = right),
+            Err(error) => format!("{:>left$}  error: {}", value, error, left = left),
+        });
 
+    let table = std::iter::once(format!("{:>left$}  {:>right$}", from, to, left = left, right = right))
+        .chain(rows)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (table, results.iter().filter(|result| result.is_err()).count())
+}
 
 fn part1(input : String) -> String {
-    let decimal_sum_value = input.lines()
-        .map(|line| snafu_to_decimal(line))
-        .sum();
-    decimal_to_snafu(decimal_sum_value)
+    input.lines()
+        .map(|line| line.parse::<Numeral<Snafu>>().unwrap())
+        .sum::<Numeral<Snafu>>()
+        .to_string()
 }
 
 fn part2(_input : String) -> String {
@@ -115,4 +105,20 @@ mod tests {
         let input = include_str!("../../input/input_25.txt");
         assert_eq!("2-=0-=-2=111=220=100", solve(input.to_string(), Part1));
     }
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(Ok("1=11-2".to_string()), Conversion::Encode.convert("2022"));
+        assert_eq!(Ok("314159265".to_string()), Conversion::Decode.convert("1121-1110-1=0"));
+        assert_eq!(Ok("-2".to_string()), Conversion::Decode.convert("="));
+        assert_eq!(Err("invalid digit '3' at position 2 of '13'".to_string()), Conversion::Decode.convert("13"));
+        assert_eq!(Err("invalid digit 'x' at position 3 of '12x'".to_string()), Conversion::Encode.convert("12x"));
+
+        assert_eq!(("decimal    snafu
+      1        1
+   2022   1=11-2
+  12345  1-0---0
+   -7.5  error: invalid digit '.' at position 3 of '-7.5'".to_string(), 1),
+                   conversion_table(Conversion::Encode, &["1", "2022", "12345", "-7.5"]));
+    }
 }
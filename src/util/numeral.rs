@@ -0,0 +1,296 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+// A positional numeral system. Digit values run from MIN_DIGIT up, one per character of the
+// alphabet. MIN_DIGIT is 0 for a standard base, which writes negative numbers with a leading
+// '-', and negative for a balanced base, which needs no sign.
+pub trait NumeralSystem: Clone + PartialEq + fmt::Debug {
+    const ALPHABET:&'static str;
+    const MIN_DIGIT:i64;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snafu;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal;
+
+impl NumeralSystem for Snafu {
+    const ALPHABET:&'static str = "=-012";
+    const MIN_DIGIT:i64 = -2;
+}
+
+impl NumeralSystem for Decimal {
+    const ALPHABET:&'static str = "0123456789";
+    const MIN_DIGIT:i64 = 0;
+}
+
+// Arbitrary precision number, digits least significant first without trailing zeros
+#[derive(Debug, Clone, PartialEq)]
+pub struct Numeral<S:NumeralSystem> {
+    digits:Vec<i64>,
+    // Only used by standard systems
+    negative:bool,
+    system:PhantomData<S>,
+}
+
+impl<S:NumeralSystem> Numeral<S> {
+    fn base() -> i64 {
+        let base = S::ALPHABET.chars().count() as i64;
+
+        // Other digit ranges can't write every number, carrying would never end
+        assert!(S::MIN_DIGIT == 0 || S::MIN_DIGIT == -(base - 1) / 2,
+                "digits of a base {} system must start at 0 or {}, not {}", base, -(base - 1) / 2, S::MIN_DIGIT);
+        base
+    }
+
+    pub fn zero() -> Numeral<S> {
+        Self::normalize(&[])
+    }
+
+    // Digit values in any range, least significant first
+    fn normalize(values:&[i64]) -> Numeral<S> {
+        // Wide enough that neither negating nor carrying overflows
+        let values = values.iter().map(|&value| value as i128).collect::<Vec<_>>();
+
+        match Self::carry(&values) {
+            Some(digits) => Numeral{digits, negative:false, system:PhantomData},
+            None => {
+                let digits = Self::carry(&values.iter().map(|value| -value).collect::<Vec<_>>()).unwrap();
+                Numeral{negative:!digits.is_empty(), digits, system:PhantomData}
+            }
+        }
+    }
+
+    // Brings every value into the digit range by moving the excess up. None when a standard
+    // system would need an endless run of digits, i.e. the value is negative.
+    fn carry(values:&[i128]) -> Option<Vec<i64>> {
+        let (base, min_digit) = (Self::base() as i128, S::MIN_DIGIT as i128);
+        let digit = |value:i128| (value - min_digit).rem_euclid(base) + min_digit;
+
+        let mut digits = vec![];
+        let mut carry = 0;
+
+        for value in values {
+            let value = value + carry;
+            digits.push(digit(value) as i64);
+            carry = (value - digit(value)) / base;
+        }
+
+        while carry != 0 {
+            if S::MIN_DIGIT == 0 && carry < 0 {
+                return None;
+            }
+            digits.push(digit(carry) as i64);
+            carry = (carry - digit(carry)) / base;
+        }
+
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        Some(digits)
+    }
+
+    // Digit values with the sign applied
+    fn signed(&self) -> Vec<i64> {
+        let sign = if self.negative { -1 } else { 1 };
+        self.digits.iter().map(|digit| digit * sign).collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn to_i64(&self) -> Option<i64> {
+        // Balanced digits can pass beyond i64 on the way to a value within it
+        self.signed().iter().rev()
+            .try_fold(0_i128, |acc, digit| acc.checked_mul(Self::base() as i128)?.checked_add(*digit as i128))
+            .and_then(|value| i64::try_from(value).ok())
+    }
+
+    pub fn convert<T:NumeralSystem>(&self) -> Numeral<T> {
+        let base = Numeral::<T>::from(Self::base());
+
+        self.signed().iter().rev()
+            .fold(Numeral::zero(), |acc, digit| acc * base.clone() + Numeral::from(*digit))
+    }
+}
+
+impl<S:NumeralSystem> From<i64> for Numeral<S> {
+    fn from(value:i64) -> Numeral<S> {
+        Self::normalize(&[value])
+    }
+}
+
+impl<S:NumeralSystem> FromStr for Numeral<S> {
+    type Err = String;
+
+    fn from_str(input:&str) -> Result<Numeral<S>, String> {
+        let (sign, digits) = match input.strip_prefix('-') {
+            Some(digits) if S::MIN_DIGIT == 0 => (-1, digits),
+            _ => (1, input),
+        };
+
+        if digits.is_empty() {
+            return Err(format!("'{}' has no digits", input));
+        }
+
+        let offset = input.len() - digits.len();
+        let values = digits.chars().enumerate()
+            .map(|(index, ch)| S::ALPHABET.chars().position(|digit| digit == ch)
+                .map(|position| (position as i64 + S::MIN_DIGIT) * sign)
+                .ok_or(format!("invalid digit '{}' at position {} of '{}'", ch, offset + index + 1, input)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::normalize(&values.into_iter().rev().collect::<Vec<_>>()))
+    }
+}
+
+impl<S:NumeralSystem> fmt::Display for Numeral<S> {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let symbol = |digit:&i64| S::ALPHABET.chars().nth((digit - S::MIN_DIGIT) as usize).unwrap();
+
+        if self.digits.is_empty() {
+            return write!(f, "{}", symbol(&0));
+        }
+
+        let digits = self.digits.iter().rev().map(symbol).collect::<String>();
+        write!(f, "{}{}", if self.negative { "-" } else { "" }, digits)
+    }
+}
+
+impl<S:NumeralSystem> Add for Numeral<S> {
+    type Output = Numeral<S>;
+
+    fn add(self, other:Numeral<S>) -> Numeral<S> {
+        let (left, right) = (self.signed(), other.signed());
+        let values = (0..left.len().max(right.len()))
+            .map(|index| left.get(index).unwrap_or(&0) + right.get(index).unwrap_or(&0))
+            .collect::<Vec<_>>();
+
+        Self::normalize(&values)
+    }
+}
+
+impl<S:NumeralSystem> Neg for Numeral<S> {
+    type Output = Numeral<S>;
+
+    fn neg(self) -> Numeral<S> {
+        Self::normalize(&self.signed().iter().map(|digit| -digit).collect::<Vec<_>>())
+    }
+}
+
+impl<S:NumeralSystem> Sub for Numeral<S> {
+    type Output = Numeral<S>;
+
+    fn sub(self, other:Numeral<S>) -> Numeral<S> {
+        self + -other
+    }
+}
+
+impl<S:NumeralSystem> Mul for Numeral<S> {
+    type Output = Numeral<S>;
+
+    // Long multiplication, carries are only resolved at the end
+    fn mul(self, other:Numeral<S>) -> Numeral<S> {
+        let (left, right) = (self.signed(), other.signed());
+        let mut values = vec![0; left.len() + right.len()];
+
+        for (i, a) in left.iter().enumerate() {
+            for (j, b) in right.iter().enumerate() {
+                values[i + j] += a * b;
+            }
+        }
+
+        Self::normalize(&values)
+    }
+}
+
+impl<S:NumeralSystem> Sum for Numeral<S> {
+    fn sum<I:Iterator<Item=Numeral<S>>>(iter:I) -> Numeral<S> {
+        iter.fold(Numeral::zero(), |acc, value| acc + value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct BalancedTernary;
+
+    impl NumeralSystem for BalancedTernary {
+        const ALPHABET:&'static str = "-0+";
+        const MIN_DIGIT:i64 = -1;
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Shifted;
+
+    impl NumeralSystem for Shifted {
+        const ALPHABET:&'static str = "=-0";
+        const MIN_DIGIT:i64 = -2;
+    }
+
+    fn snafu(input:&str) -> Numeral<Snafu> {
+        input.parse().unwrap()
+    }
+
+    fn decimal(input:&str) -> Numeral<Decimal> {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn test_snafu() {
+        let table = [(1, "1"), (3, "1="), (8, "2="), (10, "20"), (15, "1=0"), (2022, "1=11-2"),
+            (12345, "1-0---0"), (314159265, "1121-1110-1=0"), (0, "0"), (-7, "-="), (-4, "-1")];
+
+        for (value, text) in table.iter() {
+            assert_eq!(*text, Numeral::<Snafu>::from(*value).to_string());
+            assert_eq!(Some(*value), snafu(text).to_i64());
+        }
+
+        assert_eq!(Err("invalid digit '3' at position 3 of '1=3'".to_string()), "1=3".parse::<Numeral<Snafu>>());
+        assert_eq!(Err("'' has no digits".to_string()), "".parse::<Numeral<Snafu>>());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!("1=11-2", (snafu("1=11-1") + snafu("1")).to_string());
+        assert_eq!("=", (snafu("1=") - snafu("10")).to_string());
+        assert_eq!(snafu("1=11-2"), Numeral::from(2) * Numeral::from(3) * Numeral::from(337));
+
+        assert_eq!("100000000000000000000", (decimal("99999999999999999999") + decimal("1")).to_string());
+        assert_eq!("-99999999999999999999", (decimal("1") - decimal("100000000000000000000")).to_string());
+        assert_eq!("121932631137021795226185032733622923332237463801111263526900",
+                   (decimal("123456789012345678901234567890") * decimal("987654321098765432109876543210")).to_string());
+        assert_eq!("-12", (decimal("-3") * decimal("4")).to_string());
+        assert_eq!("0", (decimal("-3") + decimal("3")).to_string());
+        assert_eq!(None, decimal("9223372036854775808").to_i64());
+        assert_eq!(Some(i64::MIN), decimal("-9223372036854775808").to_i64());
+        assert_eq!("-9223372036854775808", Numeral::<Decimal>::from(i64::MIN).to_string());
+        assert_eq!(Some(i64::MIN), Numeral::<Snafu>::from(i64::MIN).to_i64());
+        assert_eq!(Err("invalid digit '-' at position 2 of '1-2'".to_string()), "1-2".parse::<Numeral<Decimal>>());
+    }
+
+    #[test]
+    fn test_convert() {
+        let big = decimal("123456789012345678901234567890");
+        let converted = big.convert::<Snafu>();
+
+        assert_eq!(big, converted.convert::<Decimal>());
+        assert_eq!(big, converted.convert::<BalancedTernary>().convert::<Decimal>());
+        assert_eq!("+0--", Numeral::<BalancedTernary>::from(23).to_string());
+        assert_eq!("-0++", decimal("-23").convert::<BalancedTernary>().to_string());
+        assert_eq!("-23", "-0++".parse::<Numeral<BalancedTernary>>().unwrap().convert::<Decimal>().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "digits of a base 3 system must start at 0 or -1, not -2")]
+    fn test_inconsistent_system() {
+        let _ = Numeral::<Shifted>::from(7);
+    }
+}